@@ -0,0 +1,109 @@
+//! Example program depositing through affiliate program by cpi
+//!
+//! Tokens are held by a depositor PDA of this program (like a smart account), which signs
+//! `deposit_for` so the lp position is credited to user PDA of the beneficiary.
+#![deny(rustdoc::all)]
+#![allow(rustdoc::missing_doc_code_examples)]
+#![warn(missing_docs)]
+
+use affiliate::cpi::accounts::DepositFor;
+use affiliate::program::Affiliate;
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+declare_id!("4kAyt6YUNrWQqTJrM5AL4kvUWJNdqpYxZnEgVYoQdfPR");
+
+/// Depositor prefix
+pub const DEPOSITOR_PREFIX: &[u8] = b"depositor";
+
+/// affiliate cpi caller program
+#[program]
+pub mod affiliate_cpi_caller {
+    use super::*;
+
+    /// deposit tokens held by depositor PDA of beneficiary through affiliate program
+    #[allow(clippy::needless_lifetimes)]
+    pub fn deposit<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, Deposit<'info>>,
+        token_amount: u64,
+        minimum_lp_token_amount: u64,
+    ) -> Result<()> {
+        let beneficiary_key = ctx.accounts.beneficiary.key();
+        let depositor_seeds = &[
+            DEPOSITOR_PREFIX,
+            beneficiary_key.as_ref(),
+            &[*ctx
+                .bumps
+                .get("depositor")
+                .ok_or(CpiCallerError::InvalidBump)?],
+        ];
+
+        affiliate::cpi::deposit_for(
+            CpiContext::new_with_signer(
+                ctx.accounts.affiliate_program.to_account_info(),
+                DepositFor {
+                    partner: ctx.accounts.partner.to_account_info(),
+                    user: ctx.accounts.user.to_account_info(),
+                    vault_program: ctx.accounts.vault_program.to_account_info(),
+                    vault: ctx.accounts.vault.to_account_info(),
+                    token_vault: ctx.accounts.token_vault.to_account_info(),
+                    vault_lp_mint: ctx.accounts.vault_lp_mint.to_account_info(),
+                    depositor_token: ctx.accounts.depositor_token.to_account_info(),
+                    user_lp: ctx.accounts.user_lp.to_account_info(),
+                    depositor: ctx.accounts.depositor.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                &[&depositor_seeds[..]],
+            ),
+            token_amount,
+            minimum_lp_token_amount,
+        )
+    }
+}
+
+/// Deposit struct
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    /// CHECK: depositor PDA holding tokens of beneficiary, signs deposit_for
+    #[account(seeds = [DEPOSITOR_PREFIX, beneficiary.key().as_ref()], bump)]
+    pub depositor: UncheckedAccount<'info>,
+    /// token account of depositor
+    #[account(mut, constraint = depositor_token.owner == depositor.key())]
+    pub depositor_token: Box<Account<'info, TokenAccount>>,
+    /// beneficiary, owner of the user account in affiliate program
+    pub beneficiary: Signer<'info>,
+
+    /// CHECK: checked by affiliate program
+    #[account(mut)]
+    pub partner: UncheckedAccount<'info>,
+    /// CHECK: user account of beneficiary, checked by affiliate program
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: checked by affiliate program
+    pub vault_program: UncheckedAccount<'info>,
+    /// CHECK: checked by affiliate program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: checked by affiliate program
+    #[account(mut)]
+    pub token_vault: UncheckedAccount<'info>,
+    /// CHECK: checked by affiliate program
+    #[account(mut)]
+    pub vault_lp_mint: UncheckedAccount<'info>,
+    /// CHECK: checked by affiliate program
+    #[account(mut)]
+    pub user_lp: UncheckedAccount<'info>,
+
+    /// Affiliate program
+    pub affiliate_program: Program<'info, Affiliate>,
+    /// Token program account
+    pub token_program: Program<'info, Token>,
+}
+
+/// CpiCallerError struct
+#[error_code]
+pub enum CpiCallerError {
+    /// InvalidBump
+    #[msg("Invalid bump")]
+    InvalidBump,
+}
@@ -6,10 +6,14 @@
 #![warn(missing_docs)]
 
 use anchor_lang::prelude::*;
+use anchor_lang::{Discriminator, ZeroCopy};
 pub mod vault_utils;
 use crate::vault_utils::PRICE_PRECISION;
 use crate::vault_utils::{MercurialVault, VaultUtils, VirtualPrice};
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token::{
+    self, spl_token, CloseAccount, InitializeAccount3, Mint, Token, TokenAccount, Transfer,
+};
 use mercurial_vault::state::Vault;
 use mercurial_vault::{PERFORMANCE_FEE_DENOMINATOR, PERFORMANCE_FEE_NUMERATOR};
 use std::str::FromStr;
@@ -25,23 +29,200 @@ pub fn get_admin_address() -> Pubkey {
 /// Fee denominator
 const FEE_DENOMINATOR: u128 = 10_000;
 const DEFAULT_FEE_RATIO: u64 = 5_000; // 50%
+/// Points multiplier denominator
+const POINTS_MULTIPLIER_DENOMINATOR: u128 = 10_000;
+const DEFAULT_POINTS_MULTIPLIER: u64 = 10_000; // 1x
+const MAX_POINTS_MULTIPLIER: u64 = 100_000; // 10x
+
+/// Partner account space, partners created with 200 bytes must be resized by resize_partner
+pub const PARTNER_SPACE: usize = 400; // data + buffer
+
+/// Seed prefix of partner profile
+pub const PARTNER_PROFILE_PREFIX: &[u8] = b"partner_profile";
+/// Max length of partner display name
+pub const MAX_PROFILE_NAME_LEN: usize = 32;
+/// Max length of partner website and logo uri
+pub const MAX_PROFILE_URI_LEN: usize = 128;
+/// Max length of partner contact
+pub const MAX_PROFILE_CONTACT_LEN: usize = 64;
+
+/// Seed prefix of config
+pub const CONFIG_PREFIX: &[u8] = b"config";
+/// Max number of funders in config
+pub const MAX_FUNDERS: usize = 10;
+
+/// Seed prefix of partner group
+pub const PARTNER_GROUP_PREFIX: &[u8] = b"partner_group";
+
+/// Seed prefix of partner vesting
+pub const PARTNER_VESTING_PREFIX: &[u8] = b"partner_vesting";
+/// Seed prefix of partner vesting escrow
+pub const VESTING_ESCROW_PREFIX: &[u8] = b"vesting_escrow";
+
+/// Seed prefix of payout receipt
+pub const PAYOUT_RECEIPT_PREFIX: &[u8] = b"payout_receipt";
+
+/// Seed prefix of referral code
+pub const REFERRAL_CODE_PREFIX: &[u8] = b"referral_code";
+/// Max length of referral code
+pub const MAX_REFERRAL_CODE_LEN: usize = 16;
+
+/// Campaign prefix
+pub const CAMPAIGN_PREFIX: &[u8] = b"campaign";
+
+/// Number of accounts per leg of basket deposit: partner, user, vault, token_vault, vault_lp_mint, user_token, user_lp
+pub const BASKET_LEG_ACCOUNTS: usize = 7;
+
+/// Temporary wsol prefix, account wraps native SOL of user during deposit/withdraw
+pub const TEMP_WSOL_PREFIX: &[u8] = b"temp_wsol";
+
+/// Rebate escrow prefix, escrow holds rebate funded by partner for its users
+pub const REBATE_ESCROW_PREFIX: &[u8] = b"rebate_escrow";
 
 /// affiliate program
 #[program]
 pub mod affiliate {
     use super::*;
-    /// function can be only called by admin
+    /// function can be only called by admin or partner manager
     pub fn init_partner(ctx: Context<InitPartner>) -> Result<()> {
-        let partner = &mut ctx.accounts.partner;
+        let mut partner = ctx.accounts.partner.load_init()?;
         partner.vault = ctx.accounts.vault.key();
         partner.partner_token = ctx.accounts.partner_token.key();
         partner.fee_ratio = DEFAULT_FEE_RATIO;
+        partner.points_multiplier = DEFAULT_POINTS_MULTIPLIER;
+        partner.users_backfilled = 1;
         Ok(())
     }
 
     /// function can be only called by admin
+    pub fn init_config(ctx: Context<InitConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.bump = *ctx.bumps.get("config").ok_or(VaultError::InvalidBump)?;
+        Ok(())
+    }
+
+    /// function can be only called by admin, set accounts that are allowed to fund partners
+    pub fn set_funders(
+        ctx: Context<UpdateConfig>,
+        treasury: Pubkey,
+        funders: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(funders.len() <= MAX_FUNDERS, VaultError::TooManyFunders);
+        let config = &mut ctx.accounts.config;
+        config.treasury = treasury;
+        config.funders = funders;
+        Ok(())
+    }
+
+    /// function can be only called by super admin, assign roles for day-to-day operations
+    pub fn set_roles(
+        ctx: Context<UpdateConfig>,
+        partner_manager: Pubkey,
+        fee_manager: Pubkey,
+        pauser: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.partner_manager = partner_manager;
+        config.fee_manager = fee_manager;
+        config.pauser = pauser;
+        Ok(())
+    }
+
+    /// function can be only called by admin or pauser, only admin can unpause
+    pub fn pause_partner(ctx: Context<PausePartner>, paused: bool) -> Result<()> {
+        if !paused {
+            require_keys_eq!(
+                ctx.accounts.admin.key(),
+                get_admin_address(),
+                VaultError::InvalidAuthority
+            );
+        }
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        partner.paused = paused.into();
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner manager, partner must not have users or outstanding fee,
+    /// balance of rebate escrow is returned to partner token and the escrow is closed
+    pub fn close_partner(ctx: Context<ClosePartner>) -> Result<()> {
+        let (vault_key, partner_token_key) = {
+            let partner = ctx.accounts.partner.load()?;
+            // user_count and total_lp only cover every user once legacy users are counted
+            require!(
+                partner.users_backfilled != 0,
+                VaultError::PartnerUsersNotBackfilled
+            );
+            // rebate left after every user is closed is not owed to anyone
+            require!(
+                partner.user_count == 0
+                    && partner.total_lp == 0
+                    && partner.outstanding_fee == 0
+                    && partner.spilled_fee == 0,
+                VaultError::PartnerNotEmpty
+            );
+            if partner.group != Pubkey::default() {
+                let group = ctx
+                    .accounts
+                    .partner_group
+                    .as_mut()
+                    .ok_or(VaultError::PartnerNotInGroup)?;
+                require_keys_eq!(group.key(), partner.group, VaultError::PartnerNotInGroup);
+                group.partner_count = group.partner_count.saturating_sub(1);
+            }
+            (partner.vault, partner.partner_token)
+        };
+
+        // rebate escrow is only created by init_rebate_escrow
+        let rebate_escrow = ctx.accounts.rebate_escrow.to_account_info();
+        if rebate_escrow.data_is_empty() {
+            return Ok(());
+        }
+        let escrow_amount = Account::<TokenAccount>::try_from(&rebate_escrow)?.amount;
+        let (_, partner_bump) = Pubkey::find_program_address(
+            &[vault_key.as_ref(), partner_token_key.as_ref()],
+            &crate::ID,
+        );
+        let partner_seeds = &[
+            vault_key.as_ref(),
+            partner_token_key.as_ref(),
+            &[partner_bump],
+        ];
+        // partner signs the cpis below, so its data is not borrowed anymore
+        if escrow_amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: rebate_escrow.clone(),
+                        to: ctx.accounts.partner_token.to_account_info(),
+                        authority: ctx.accounts.partner.to_account_info(),
+                    },
+                    &[&partner_seeds[..]],
+                ),
+                escrow_amount,
+            )?;
+        }
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: rebate_escrow,
+                destination: ctx.accounts.admin.to_account_info(),
+                authority: ctx.accounts.partner.to_account_info(),
+            },
+            &[&partner_seeds[..]],
+        ))
+    }
+
+    /// function can be only called by admin or partner manager, after every user created before
+    /// partner counters is counted with count_user
+    pub fn set_users_backfilled(ctx: Context<SetUsersBackfilled>) -> Result<()> {
+        ctx.accounts.partner.load_mut()?.users_backfilled = 1;
+        Ok(())
+    }
+
+    /// function can be only called by admin or fee manager
     pub fn update_fee_ratio(ctx: Context<UpdateFeeRatio>, fee_ratio: u64) -> Result<()> {
-        let partner = &mut ctx.accounts.partner;
+        let mut partner = ctx.accounts.partner.load_mut()?;
         if fee_ratio > FEE_DENOMINATOR as u64 {
             return Err(VaultError::InvalidFeeRatio.into());
         }
@@ -49,21 +230,386 @@ pub mod affiliate {
         Ok(())
     }
 
+    /// function can be only called by admin or partner, None removes the override,
+    /// partner can only lower fee ratio of its users
+    pub fn update_user_fee_ratio(
+        ctx: Context<UpdateUserFeeRatio>,
+        fee_ratio: Option<u64>,
+    ) -> Result<()> {
+        if let Some(fee_ratio) = fee_ratio {
+            if fee_ratio > FEE_DENOMINATOR as u64 {
+                return Err(VaultError::InvalidFeeRatio.into());
+            }
+            if ctx.accounts.authority.key() != get_admin_address()
+                && fee_ratio > ctx.accounts.partner.load()?.fee_ratio
+            {
+                return Err(VaultError::InvalidFeeRatio.into());
+            }
+        }
+        ctx.accounts
+            .user
+            .load_mut()?
+            .set_fee_ratio_override(fee_ratio);
+
+        emit!(UserFeeRatio {
+            user: ctx.accounts.user.key(),
+            partner: ctx.accounts.partner.key(),
+            fee_ratio,
+        });
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner
+    pub fn update_gated_onboarding(
+        ctx: Context<UpdatePartnerSetting>,
+        require_partner_signature: bool,
+    ) -> Result<()> {
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        partner.require_partner_signature = require_partner_signature.into();
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner, ratio of accrued fee credited back to users
+    pub fn update_rebate_ratio(
+        ctx: Context<UpdatePartnerSetting>,
+        rebate_ratio: u64,
+    ) -> Result<()> {
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        if rebate_ratio > FEE_DENOMINATOR as u64 {
+            return Err(VaultError::InvalidFeeRatio.into());
+        }
+        partner.rebate_ratio = rebate_ratio;
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner, create campaign to tag deposits with
+    pub fn init_campaign(ctx: Context<InitCampaign>, campaign_id: u32) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.partner = ctx.accounts.partner.key();
+        campaign.campaign_id = campaign_id;
+        campaign.bump = *ctx.bumps.get("campaign").ok_or(VaultError::InvalidBump)?;
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner, multiplier of points accrued by users,
+    /// 0 falls back to the default multiplier
+    pub fn update_points_multiplier(
+        ctx: Context<UpdatePartnerSetting>,
+        points_multiplier: u64,
+    ) -> Result<()> {
+        if points_multiplier > MAX_POINTS_MULTIPLIER {
+            return Err(VaultError::InvalidPointsMultiplier.into());
+        }
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        partner.points_multiplier = points_multiplier;
+        Ok(())
+    }
+
+    /// accrue points of user up to now, can be called by anyone
+    pub fn refresh_points(ctx: Context<RefreshPoints>) -> Result<()> {
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
+        let points_multiplier = ctx.accounts.partner.load()?.get_points_multiplier();
+        accrue_user_points(
+            ctx.accounts.partner.key(),
+            ctx.accounts.user.key(),
+            &mut *ctx.accounts.user.load_mut()?,
+            points_multiplier,
+            current_time,
+        );
+        Ok(())
+    }
+
+    /// init rebate escrow of partner, can be called by anyone
+    pub fn init_rebate_escrow(_ctx: Context<InitRebateEscrow>) -> Result<()> {
+        Ok(())
+    }
+
+    /// claim outstanding rebate of user, capped at balance of rebate escrow
+    pub fn claim_rebate(ctx: Context<ClaimRebate>) -> Result<()> {
+        let amount = ctx
+            .accounts
+            .user
+            .load()?
+            .outstanding_rebate()
+            .min(ctx.accounts.rebate_escrow.amount);
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let vault_key = ctx.accounts.vault.key();
+        let partner_token_key = ctx.accounts.partner_token.key();
+        let partner_seeds = &[
+            vault_key.as_ref(),
+            partner_token_key.as_ref(),
+            &[*ctx.bumps.get("partner").ok_or(VaultError::InvalidBump)?],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.rebate_escrow.to_account_info(),
+                    to: ctx.accounts.owner_token.to_account_info(),
+                    authority: ctx.accounts.partner.to_account_info(),
+                },
+                &[&partner_seeds[..]],
+            ),
+            amount,
+        )?;
+
+        // partner signs the transfer above, so its data is only borrowed after the cpi
+        ctx.accounts
+            .user
+            .load_mut()?
+            .claim_rebate(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        partner.outstanding_rebate = partner.outstanding_rebate.saturating_sub(amount);
+
+        emit!(RebateClaimed {
+            user: ctx.accounts.user.key(),
+            partner: ctx.accounts.partner.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// function can be only called by partner wallet
+    pub fn init_partner_profile(
+        ctx: Context<InitPartnerProfile>,
+        name: String,
+        website_uri: String,
+        logo_uri: String,
+        contact: String,
+    ) -> Result<()> {
+        let profile = &mut ctx.accounts.partner_profile;
+        profile.partner_wallet = ctx.accounts.partner_wallet.key();
+        profile.bump = *ctx
+            .bumps
+            .get("partner_profile")
+            .ok_or(VaultError::InvalidBump)?;
+        profile.update(name, website_uri, logo_uri, contact)
+    }
+
+    /// function can be only called by partner wallet
+    pub fn update_partner_profile(
+        ctx: Context<UpdatePartnerProfile>,
+        name: String,
+        website_uri: String,
+        logo_uri: String,
+        contact: String,
+    ) -> Result<()> {
+        let profile = &mut ctx.accounts.partner_profile;
+        profile.update(name, website_uri, logo_uri, contact)
+    }
+
+    /// function can be only called by admin, partner manager or partner wallet, wallet must own a partner
+    pub fn register_referral_code(ctx: Context<RegisterReferralCode>, code: String) -> Result<()> {
+        require!(
+            ReferralCode::is_valid_code(&code),
+            VaultError::InvalidReferralCode
+        );
+        let referral_code = &mut ctx.accounts.referral_code;
+        referral_code.code = code;
+        referral_code.partner_wallet = ctx.accounts.partner_wallet.key();
+        referral_code.payer = ctx.accounts.authority.key();
+        referral_code.bump = *ctx
+            .bumps
+            .get("referral_code")
+            .ok_or(VaultError::InvalidBump)?;
+        Ok(())
+    }
+
+    /// function can be only called by admin, partner manager or partner wallet of the code, point referral code
+    /// to another partner wallet
+    pub fn reassign_referral_code(ctx: Context<ReassignReferralCode>) -> Result<()> {
+        let referral_code = &mut ctx.accounts.referral_code;
+        referral_code.partner_wallet = ctx.accounts.partner_wallet.key();
+        Ok(())
+    }
+
+    /// function can be only called by admin, partner manager or partner wallet of the code, rent is returned
+    /// to the payer of the code
+    pub fn close_referral_code(_ctx: Context<CloseReferralCode>) -> Result<()> {
+        Ok(())
+    }
+
+    /// function can be only called by user, partner is resolved from referral code
+    pub fn init_user_with_referral_code(
+        ctx: Context<InitUserWithReferralCode>,
+        _code: String,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.partner.load()?.require_partner_signature == 0,
+            VaultError::PartnerSignatureRequired
+        );
+
+        let mut user = ctx.accounts.user.load_init()?;
+        user.partner = ctx.accounts.partner.key();
+        user.owner = ctx.accounts.owner.key();
+        user.bump = *ctx.bumps.get("user").ok_or(VaultError::InvalidBump)?;
+        user.set_flag(UserV2::FLAG_COUNTED, true);
+
+        ctx.accounts.partner.load_mut()?.add_user();
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner manager
+    pub fn init_partner_group(ctx: Context<InitPartnerGroup>) -> Result<()> {
+        let group = &mut ctx.accounts.partner_group;
+        group.partner_wallet = ctx.accounts.partner_wallet.key();
+        group.fee_ratio = DEFAULT_FEE_RATIO;
+        group.bump = *ctx
+            .bumps
+            .get("partner_group")
+            .ok_or(VaultError::InvalidBump)?;
+        Ok(())
+    }
+
+    /// function can be only called by admin or partner manager
+    pub fn link_partner_group(ctx: Context<LinkPartnerGroup>) -> Result<()> {
+        let group_key = ctx.accounts.partner_group.key();
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        if partner.group == group_key {
+            // already linked
+            return Ok(());
+        }
+        require_keys_eq!(
+            partner.group,
+            Pubkey::default(),
+            VaultError::PartnerAlreadyInGroup
+        );
+        partner.group = group_key;
+
+        let group = &mut ctx.accounts.partner_group;
+        group.partner_count = group.partner_count.saturating_add(1);
+        Ok(())
+    }
+
+    /// function can be only called by admin or fee manager, every partner of the group is passed as remaining accounts
+    pub fn update_group_fee_ratio<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, UpdateGroupFeeRatio<'info>>,
+        fee_ratio: u64,
+    ) -> Result<()> {
+        if fee_ratio > FEE_DENOMINATOR as u64 {
+            return Err(VaultError::InvalidFeeRatio.into());
+        }
+        // fee ratio of the group must match every linked partner
+        require!(
+            usize::try_from(ctx.accounts.partner_group.partner_count).ok()
+                == Some(ctx.remaining_accounts.len()),
+            VaultError::PartnerGroupIncomplete
+        );
+        let group_key = ctx.accounts.partner_group.key();
+        let mut partner_keys: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        for partner_info in ctx.remaining_accounts.iter() {
+            require!(
+                !partner_keys.contains(partner_info.key),
+                VaultError::PartnerGroupIncomplete
+            );
+            partner_keys.push(partner_info.key());
+            let partner = AccountLoader::<PartnerV2>::try_from(partner_info)?;
+            let mut partner = partner.load_mut()?;
+            require_keys_eq!(partner.group, group_key, VaultError::PartnerNotInGroup);
+            partner.fee_ratio = fee_ratio;
+        }
+        ctx.accounts.partner_group.fee_ratio = fee_ratio;
+        Ok(())
+    }
+
     /// function can be only called by user
     pub fn init_user(ctx: Context<InitUser>) -> Result<()> {
-        let user = &mut ctx.accounts.user;
+        if ctx.accounts.partner.load()?.require_partner_signature != 0 {
+            // partner wallet must co-sign when onboarding is gated
+            let partner_token = ctx
+                .accounts
+                .partner_token
+                .as_ref()
+                .ok_or(VaultError::PartnerSignatureRequired)?;
+            let partner_authority = ctx
+                .accounts
+                .partner_authority
+                .as_ref()
+                .ok_or(VaultError::PartnerSignatureRequired)?;
+            require_keys_eq!(
+                partner_authority.key(),
+                partner_token.owner,
+                VaultError::PartnerSignatureRequired
+            );
+        }
+
+        let mut user = ctx.accounts.user.load_init()?;
         user.partner = ctx.accounts.partner.key();
         user.owner = ctx.accounts.owner.key();
         user.bump = *ctx.bumps.get("user").ok_or(VaultError::InvalidBump)?;
+        user.set_flag(UserV2::FLAG_COUNTED, true);
+
+        ctx.accounts.partner.load_mut()?.add_user();
         Ok(())
     }
 
     /// function can be only called by user
     pub fn init_user_permissionless(ctx: Context<InitUserPermissionless>) -> Result<()> {
-        let user = &mut ctx.accounts.user;
+        require!(
+            ctx.accounts.partner.load()?.require_partner_signature == 0,
+            VaultError::PartnerSignatureRequired
+        );
+
+        let mut user = ctx.accounts.user.load_init()?;
         user.partner = ctx.accounts.partner.key();
         user.owner = ctx.accounts.owner.key();
         user.bump = *ctx.bumps.get("user").ok_or(VaultError::InvalidBump)?;
+        user.set_flag(UserV2::FLAG_COUNTED, true);
+
+        ctx.accounts.partner.load_mut()?.add_user();
+        Ok(())
+    }
+
+    /// close user account, user lp token account must be empty, rebate that is not claimed is forfeited
+    pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
+        let partner_key = ctx.accounts.partner.key();
+        let owner_key = ctx.accounts.owner.key();
+        let user_bump = ctx.accounts.user.load()?.bump;
+        let user_seeds = &[partner_key.as_ref(), owner_key.as_ref(), &[user_bump]];
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.user_lp.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+            &[&user_seeds[..]],
+        ))?;
+
+        // users created before partner counters are not tracked in user_count and total_lp
+        let user = ctx.accounts.user.load()?;
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        if user.counted() {
+            partner.remove_user(user.lp_token);
+        }
+
+        // claim_rebate is capped at the escrow balance, so closing can't wait for the rest
+        let forfeited_rebate = user.outstanding_rebate();
+        if forfeited_rebate > 0 {
+            partner.outstanding_rebate =
+                partner.outstanding_rebate.saturating_sub(forfeited_rebate);
+            emit!(RebateForfeited {
+                user: ctx.accounts.user.key(),
+                partner: ctx.accounts.partner.key(),
+                amount: forfeited_rebate,
+            });
+        }
+        Ok(())
+    }
+
+    /// count user created before partner counters in user_count and total_lp, can be called by anyone
+    pub fn count_user(ctx: Context<CountUser>) -> Result<()> {
+        ctx.accounts
+            .partner
+            .load_mut()?
+            .count_user(&mut *ctx.accounts.user.load_mut()?);
         Ok(())
     }
 
@@ -73,7 +619,41 @@ pub mod affiliate {
         ctx: Context<'a, 'b, 'c, 'info, DepositWithdrawLiquidity>,
         token_amount: u64,
         minimum_lp_token_amount: u64,
+        campaign_id: Option<u32>,
     ) -> Result<()> {
+        let (partner, user) = load_liquidity_accounts(
+            &ctx.accounts.partner,
+            &ctx.accounts.user,
+            ctx.accounts.vault.key(),
+            &ctx.accounts.owner,
+            ctx.accounts.system_program.as_ref().map(|p| p.as_ref()),
+        )?;
+        require!(partner.load()?.paused == 0, VaultError::PartnerPaused);
+
+        // wrap lamports of owner when native SOL accounts are passed
+        let wrap_sol = ctx.accounts.native_mint.is_some();
+        if wrap_sol {
+            create_temp_wsol(ctx.accounts, token_amount)?;
+        }
+
+        let campaign = match campaign_id {
+            Some(campaign_id) => {
+                let campaign = ctx
+                    .accounts
+                    .campaign
+                    .as_mut()
+                    .ok_or(VaultError::InvalidCampaign)?;
+                require!(
+                    campaign.campaign_id == campaign_id,
+                    VaultError::InvalidCampaign
+                );
+                user.load_mut()?.touch_campaign(campaign_id);
+                Some(&mut **campaign)
+            }
+            None => None,
+        };
+        let previous_lp = user.load()?.lp_token;
+
         let vault = &ctx.accounts.vault.to_account_info();
         let vault_lp_mint = &ctx.accounts.vault_lp_mint.to_account_info();
         let user_lp = &ctx.accounts.user_lp.to_account_info();
@@ -103,9 +683,175 @@ pub mod affiliate {
             &mut ctx.accounts.vault,
             &mut ctx.accounts.vault_lp_mint,
             &mut ctx.accounts.user_lp,
-            &mut ctx.accounts.partner,
-            &mut ctx.accounts.user,
+            &partner,
+            &user,
+            campaign,
+            FeeAccrualPolicy::FailClosed,
+        )?;
+        if wrap_sol {
+            close_temp_wsol(ctx.accounts)?;
+        }
+
+        emit!(UserDeposit {
+            user: user.key(),
+            partner: partner.key(),
+            campaign_id,
+            token_amount,
+            lp_amount: user.load()?.lp_token.saturating_sub(previous_lp),
+        });
+        Ok(())
+    }
+
+    /// deposit on behalf of beneficiary, depositor can be a PDA signed by calling program through cpi,
+    /// lp is minted to user PDA of beneficiary, so only beneficiary can withdraw, beneficiary is the owner
+    /// of the user account, deposit is not tagged with a campaign and native SOL is not wrapped
+    #[allow(clippy::needless_lifetimes)]
+    pub fn deposit_for<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, DepositFor>,
+        token_amount: u64,
+        minimum_lp_token_amount: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.partner.load()?.paused == 0,
+            VaultError::PartnerPaused
+        );
+        let previous_lp = ctx.accounts.user.load()?.lp_token;
+
+        let vault = &ctx.accounts.vault.to_account_info();
+        let vault_lp_mint = &ctx.accounts.vault_lp_mint.to_account_info();
+        let user_lp = &ctx.accounts.user_lp.to_account_info();
+
+        let depositor_token = &ctx.accounts.depositor_token.to_account_info();
+        let token_vault = &ctx.accounts.token_vault.to_account_info();
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let vault_program = &ctx.accounts.vault_program.to_account_info();
+        let depositor = &ctx.accounts.depositor.to_account_info();
+        update_liquidity_wrapper(
+            move || {
+                VaultUtils::deposit(
+                    vault,
+                    vault_lp_mint,
+                    depositor_token,
+                    user_lp, // mint vault lp token to user PDA of beneficiary
+                    depositor,
+                    token_vault,
+                    token_program,
+                    vault_program,
+                    token_amount,
+                    minimum_lp_token_amount,
+                )?;
+
+                Ok(())
+            },
+            &mut ctx.accounts.vault,
+            &mut ctx.accounts.vault_lp_mint,
+            &mut ctx.accounts.user_lp,
+            &ctx.accounts.partner,
+            &ctx.accounts.user,
+            None,
+            FeeAccrualPolicy::FailClosed,
         )?;
+
+        emit!(UserDeposit {
+            user: ctx.accounts.user.key(),
+            partner: ctx.accounts.partner.key(),
+            campaign_id: None,
+            token_amount,
+            lp_amount: ctx
+                .accounts
+                .user
+                .load()?
+                .lp_token
+                .saturating_sub(previous_lp),
+        });
+        Ok(())
+    }
+
+    /// deposit to several vaults in one instruction, accounts of each leg are passed as remaining accounts
+    #[allow(clippy::needless_lifetimes)]
+    pub fn deposit_basket<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, DepositBasket<'info>>,
+        token_amounts: Vec<u64>,
+        minimum_lp_token_amounts: Vec<u64>,
+    ) -> Result<()> {
+        let leg_count = token_amounts.len();
+        require!(
+            minimum_lp_token_amounts.len() == leg_count
+                && Some(ctx.remaining_accounts.len()) == leg_count.checked_mul(BASKET_LEG_ACCOUNTS),
+            VaultError::InvalidBasket
+        );
+
+        let owner = &ctx.accounts.owner.to_account_info();
+        let token_program = &ctx.accounts.token_program.to_account_info();
+        let vault_program = &ctx.accounts.vault_program.to_account_info();
+        let mut vault_keys: Vec<Pubkey> = Vec::with_capacity(leg_count);
+        for ((leg, token_amount), minimum_lp_token_amount) in ctx
+            .remaining_accounts
+            .chunks(BASKET_LEG_ACCOUNTS)
+            .zip(token_amounts)
+            .zip(minimum_lp_token_amounts)
+        {
+            let [partner, user, vault, token_vault, vault_lp_mint, user_token, user_lp] =
+                <&[AccountInfo<'info>; BASKET_LEG_ACCOUNTS]>::try_from(leg)
+                    .map_err(|_| VaultError::InvalidBasket)?;
+            // same vault in several legs would accrue fee twice
+            require!(!vault_keys.contains(vault.key), VaultError::InvalidBasket);
+            vault_keys.push(vault.key());
+
+            let partner_state = AccountLoader::<PartnerV2>::try_from(partner)?;
+            let user_state = AccountLoader::<UserV2>::try_from(user)?;
+            let mut vault_state = Account::<Vault>::try_from(vault)?;
+            let mut vault_lp_mint_state = Account::<Mint>::try_from(vault_lp_mint)?;
+            let mut user_lp_state = Account::<TokenAccount>::try_from(user_lp)?;
+            let previous_lp = {
+                let partner_data = partner_state.load()?;
+                let user_data = user_state.load()?;
+                require_keys_eq!(partner_data.vault, vault.key(), VaultError::InvalidBasket);
+                require_keys_eq!(user_data.partner, partner.key(), VaultError::InvalidBasket);
+                require_keys_eq!(user_data.owner, owner.key(), VaultError::InvalidOwner);
+                require!(partner_data.paused == 0, VaultError::PartnerPaused);
+                user_data.lp_token
+            };
+            require_keys_eq!(
+                vault_state.lp_mint,
+                vault_lp_mint.key(),
+                VaultError::InvalidBasket
+            );
+            require_keys_eq!(user_lp_state.owner, user.key(), VaultError::InvalidOwner);
+            update_liquidity_wrapper(
+                move || {
+                    VaultUtils::deposit(
+                        vault,
+                        vault_lp_mint,
+                        user_token,
+                        user_lp, // mint vault lp token to pool lp token account
+                        owner,
+                        token_vault,
+                        token_program,
+                        vault_program,
+                        token_amount,
+                        minimum_lp_token_amount,
+                    )?;
+
+                    Ok(())
+                },
+                &mut vault_state,
+                &mut vault_lp_mint_state,
+                &mut user_lp_state,
+                &partner_state,
+                &user_state,
+                None,
+                FeeAccrualPolicy::FailClosed,
+            )?;
+
+            emit!(UserDeposit {
+                user: user.key(),
+                partner: partner.key(),
+                campaign_id: None,
+                token_amount,
+                lp_amount: user_state.load()?.lp_token.saturating_sub(previous_lp),
+            });
+        }
         Ok(())
     }
 
@@ -115,17 +861,37 @@ pub mod affiliate {
         ctx: Context<'a, 'b, 'c, 'info, DepositWithdrawLiquidity>,
         unmint_amount: u64,
         min_out_amount: u64,
+        unwrap_sol: bool,
     ) -> Result<()> {
-        let partner_key = ctx.accounts.partner.key();
+        let (partner, user) = load_liquidity_accounts(
+            &ctx.accounts.partner,
+            &ctx.accounts.user,
+            ctx.accounts.vault.key(),
+            &ctx.accounts.owner,
+            ctx.accounts.system_program.as_ref().map(|p| p.as_ref()),
+        )?;
+        // withdraw to temporary wsol account and close it to owner
+        if unwrap_sol {
+            create_temp_wsol(ctx.accounts, 0)?;
+        }
+        // fee is attributed to last-touch campaign of user when campaign is passed
+        let campaign = match ctx.accounts.campaign.as_mut() {
+            Some(campaign) => {
+                require!(
+                    Some(campaign.campaign_id) == user.load()?.last_campaign_id(),
+                    VaultError::InvalidCampaign
+                );
+                Some(&mut **campaign)
+            }
+            None => None,
+        };
+        let partner_key = partner.key();
         let owner_key = ctx.accounts.owner.key();
-        let user_seeds = &[
-            partner_key.as_ref(),
-            owner_key.as_ref(),
-            &[ctx.accounts.user.bump],
-        ];
+        let user_bump = user.load()?.bump;
+        let user_seeds = &[partner_key.as_ref(), owner_key.as_ref(), &[user_bump]];
 
         let vault = &ctx.accounts.vault.to_account_info();
-        let user = &ctx.accounts.user.to_account_info();
+        let user_info = &ctx.accounts.user.to_account_info();
         let vault_lp_mint = &ctx.accounts.vault_lp_mint.to_account_info();
         let user_lp = &ctx.accounts.user_lp.to_account_info();
 
@@ -140,7 +906,7 @@ pub mod affiliate {
                     vault_lp_mint,
                     user_token,
                     user_lp,
-                    user,
+                    user_info,
                     token_vault,
                     token_program,
                     vault_program,
@@ -154,9 +920,14 @@ pub mod affiliate {
             &mut ctx.accounts.vault,
             &mut ctx.accounts.vault_lp_mint,
             &mut ctx.accounts.user_lp,
-            &mut ctx.accounts.partner,
-            &mut ctx.accounts.user,
+            &partner,
+            &user,
+            campaign,
+            FeeAccrualPolicy::FailOpen,
         )?;
+        if unwrap_sol {
+            close_temp_wsol(ctx.accounts)?;
+        }
         Ok(())
     }
 
@@ -167,13 +938,17 @@ pub mod affiliate {
         unmint_amount: u64,
         min_out_amount: u64,
     ) -> Result<()> {
-        let partner_key = ctx.accounts.partner.key();
+        let (partner, user) = load_liquidity_accounts(
+            &ctx.accounts.partner,
+            &ctx.accounts.user,
+            ctx.accounts.vault.key(),
+            &ctx.accounts.owner,
+            ctx.accounts.system_program.as_ref().map(|p| p.as_ref()),
+        )?;
+        let partner_key = partner.key();
         let owner_key = ctx.accounts.owner.key();
-        let user_seeds = &[
-            partner_key.as_ref(),
-            owner_key.as_ref(),
-            &[ctx.accounts.user.bump],
-        ];
+        let user_bump = user.load()?.bump;
+        let user_seeds = &[partner_key.as_ref(), owner_key.as_ref(), &[user_bump]];
 
         let vault = &ctx.accounts.vault.to_account_info();
         let strategy = &ctx.accounts.strategy.to_account_info();
@@ -188,7 +963,7 @@ pub mod affiliate {
         let token_vault = &ctx.accounts.token_vault.to_account_info();
         let token_program = &ctx.accounts.token_program.to_account_info();
         let vault_program = &ctx.accounts.vault_program.to_account_info();
-        let user = &ctx.accounts.user.to_account_info();
+        let user_info = &ctx.accounts.user.to_account_info();
         let remaining_accounts = ctx.remaining_accounts;
         update_liquidity_wrapper(
             move || {
@@ -203,7 +978,7 @@ pub mod affiliate {
                     fee_vault,
                     user_token,
                     user_lp,
-                    user,
+                    user_info,
                     token_program,
                     vault_program,
                     remaining_accounts,
@@ -217,8 +992,10 @@ pub mod affiliate {
             &mut ctx.accounts.vault,
             &mut ctx.accounts.vault_lp_mint,
             &mut ctx.accounts.user_lp,
-            &mut ctx.accounts.partner,
-            &mut ctx.accounts.user,
+            &partner,
+            &user,
+            None,
+            FeeAccrualPolicy::FailOpen,
         )?;
         Ok(())
     }
@@ -227,6 +1004,7 @@ pub mod affiliate {
     pub fn fund_partner<'a, 'b, 'c, 'info>(
         ctx: Context<'a, 'b, 'c, 'info, FundPartner<'info>>,
         amount: u64,
+        reference: Option<[u8; 32]>,
     ) -> Result<()> {
         token::transfer(
             CpiContext::new(
@@ -239,279 +1017,2291 @@ pub mod affiliate {
             ),
             amount,
         )?;
-        // deduct fee amount, if amount > self.outstanding_fee, then it returns MathOverflow
-        let partner = &mut ctx.accounts.partner;
-        partner.outstanding_fee = partner
-            .outstanding_fee
-            .checked_sub(amount)
-            .ok_or(VaultError::MathOverflow)?;
-        Ok(())
+        let payout_receipt_bump = *ctx
+            .bumps
+            .get("payout_receipt")
+            .ok_or(VaultError::InvalidBump)?;
+        settle_partner_fee(
+            &ctx.accounts.partner,
+            &mut ctx.accounts.payout_receipt,
+            ctx.accounts.funder.key(),
+            PayoutType::Token,
+            amount,
+            reference,
+            payout_receipt_bump,
+        )
     }
-}
 
-/// update liquidity
-pub fn update_liquidity_wrapper<'info>(
-    update_liquidity_fn: impl FnOnce() -> Result<()>,
-    vault: &mut Account<'info, Vault>,
-    vault_lp_mint: &mut Account<'info, Mint>,
-    user_lp: &mut Account<'info, TokenAccount>,
-    partner: &mut Account<'info, Partner>,
-    user: &mut Account<'info, User>,
-) -> Result<()> {
-    // accrue fee
-    let current_time = u64::try_from(Clock::get()?.unix_timestamp)
-        .ok()
-        .ok_or(VaultError::MathOverflow)?;
-    let virtual_price = vault
-        .get_virtual_price(current_time, vault_lp_mint.supply)
-        .ok_or(VaultError::MathOverflow)?;
+    /// fund partner the sharing fee, fee is vested linearly after cliff
+    pub fn fund_partner_vesting<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, FundPartnerVesting<'info>>,
+        amount: u64,
+        cliff_duration: u64,
+        duration: u64,
+        reference: Option<[u8; 32]>,
+    ) -> Result<()> {
+        require!(
+            cliff_duration <= duration,
+            VaultError::InvalidVestingSchedule
+        );
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
 
-    let fee = user
-        .get_fee(virtual_price, partner.fee_ratio)
-        .ok_or(VaultError::MathOverflow)?;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funder_token.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
 
-    msg!("fee: {}", fee);
-    emit!(PartnerFee { fee });
-    // acrrure fee for partner
-    partner.accrue_fee(fee).ok_or(VaultError::MathOverflow)?;
+        {
+            let mut partner = ctx.accounts.partner.load_mut()?;
+            let vesting = &mut ctx.accounts.partner_vesting;
+            vesting.partner = ctx.accounts.partner.key();
+            vesting.escrow = ctx.accounts.escrow.key();
+            vesting.index = partner.vesting_count;
+            vesting.total_amount = amount;
+            vesting.start_at = current_time;
+            vesting.cliff_duration = cliff_duration;
+            vesting.duration = duration;
+            vesting.bump = *ctx
+                .bumps
+                .get("partner_vesting")
+                .ok_or(VaultError::InvalidBump)?;
 
-    update_liquidity_fn()?;
+            partner.vesting_count = partner
+                .vesting_count
+                .checked_add(1)
+                .ok_or(VaultError::MathOverflow)?;
+        }
 
-    // save new user state
-    user_lp.reload()?;
-    user.set_new_state(virtual_price, user_lp.amount);
+        let payout_receipt_bump = *ctx
+            .bumps
+            .get("payout_receipt")
+            .ok_or(VaultError::InvalidBump)?;
+        settle_partner_fee(
+            &ctx.accounts.partner,
+            &mut ctx.accounts.payout_receipt,
+            ctx.accounts.funder.key(),
+            PayoutType::Vesting,
+            amount,
+            reference,
+            payout_receipt_bump,
+        )
+    }
 
-    Ok(())
-}
+    /// release vested fee to partner token, can be called by anyone
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
+        let vesting = &ctx.accounts.partner_vesting;
+        let amount = vesting
+            .get_claimable_amount(current_time)
+            .ok_or(VaultError::MathOverflow)?;
+        if amount == 0 {
+            return Ok(());
+        }
 
-/// InitPartner struct
-#[derive(Accounts)]
-pub struct InitPartner<'info> {
-    /// Vault account
-    #[account(
-            init,
+        let index = vesting.index.to_le_bytes();
+        let vesting_seeds = &[
+            PARTNER_VESTING_PREFIX,
+            vesting.partner.as_ref(),
+            index.as_ref(),
+            &[vesting.bump],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow.to_account_info(),
+                    to: ctx.accounts.partner_token.to_account_info(),
+                    authority: ctx.accounts.partner_vesting.to_account_info(),
+                },
+                &[&vesting_seeds[..]],
+            ),
+            amount,
+        )?;
+
+        let vesting = &mut ctx.accounts.partner_vesting;
+        vesting.claimed_amount = vesting
+            .claimed_amount
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// emit fee that would be accrued for user now, does not mutate state, used by simulation
+    pub fn get_user_pending_fee(ctx: Context<GetUserPendingFee>) -> Result<()> {
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
+        let virtual_price = ctx
+            .accounts
+            .vault
+            .get_virtual_price(current_time, ctx.accounts.vault_lp_mint.supply)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let partner = ctx.accounts.partner.load()?;
+        let user = ctx.accounts.user.load()?;
+        let yield_earned = user
+            .get_yield_earned(virtual_price)
+            .ok_or(VaultError::MathOverflow)?;
+        let pending_fee = user
+            .get_fee(virtual_price, user.get_fee_ratio(partner.fee_ratio))
+            .ok_or(VaultError::MathOverflow)?;
+        let pending_rebate = partner
+            .get_rebate(pending_fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        emit!(UserPendingFee {
+            user: ctx.accounts.user.key(),
+            partner: ctx.accounts.partner.key(),
+            virtual_price,
+            lp_token: user.lp_token,
+            position_amount: lp_to_amount(user.lp_token, virtual_price)
+                .ok_or(VaultError::MathOverflow)?,
+            pending_yield: yield_earned,
+            pending_fee,
+            outstanding_rebate: user.outstanding_rebate.saturating_add(pending_rebate),
+        });
+        Ok(())
+    }
+
+    /// emit summary of partner, users of partner are passed as remaining accounts to sum their pending fee,
+    /// does not mutate state, used by simulation
+    pub fn get_partner_summary<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, GetPartnerSummary<'info>>,
+    ) -> Result<()> {
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
+        let virtual_price = ctx
+            .accounts
+            .vault
+            .get_virtual_price(current_time, ctx.accounts.vault_lp_mint.supply)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let partner = ctx.accounts.partner.load()?;
+        let mut pending_fee: u64 = 0;
+        for user_info in ctx.remaining_accounts.iter() {
+            let user = AccountLoader::<UserV2>::try_from(user_info)?;
+            let user = user.load()?;
+            require_keys_eq!(
+                user.partner,
+                ctx.accounts.partner.key(),
+                VaultError::InvalidOwner
+            );
+            let fee = user
+                .get_fee(virtual_price, user.get_fee_ratio(partner.fee_ratio))
+                .ok_or(VaultError::MathOverflow)?;
+            pending_fee = pending_fee
+                .checked_add(fee)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+
+        emit!(PartnerSummary {
+            partner: ctx.accounts.partner.key(),
+            virtual_price,
+            fee_ratio: partner.fee_ratio,
+            user_count: partner.user_count,
+            total_lp: partner.total_lp,
+            total_amount: lp_to_amount(partner.total_lp, virtual_price)
+                .ok_or(VaultError::MathOverflow)?,
+            outstanding_fee: partner.outstanding_fee,
+            cumulative_fee: partner.cumulative_fee(),
+            pending_fee,
+            user_pending_count: ctx.remaining_accounts.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// resize partner account created with previous space, can be called by anyone
+    pub fn resize_partner(ctx: Context<ResizePartner>) -> Result<()> {
+        let partner = &ctx.accounts.partner;
+        {
+            let data = partner.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == Partner::DISCRIMINATOR,
+                VaultError::InvalidPartnerAccount
+            );
+        }
+        realloc_partner(
+            &ctx.accounts.partner,
+            &ctx.accounts.payer,
+            Some(&ctx.accounts.system_program),
+        )
+    }
+
+    /// migrate partner from borsh layout to zero-copy layout, partner is resized first if needed,
+    /// can be called by anyone
+    pub fn migrate_partner(ctx: Context<ResizePartner>) -> Result<()> {
+        migrate_partner_in_place(
+            &ctx.accounts.partner,
+            &ctx.accounts.payer,
+            Some(&ctx.accounts.system_program),
+        )
+    }
+
+    /// migrate user from borsh layout to zero-copy layout, can be called by anyone
+    pub fn migrate_user(ctx: Context<MigrateUser>) -> Result<()> {
+        migrate_user_in_place(&ctx.accounts.user)
+    }
+
+    /// function can be only called by admin or partner, set lp token account to receive fee in vault lp
+    pub fn set_partner_lp(ctx: Context<SetPartnerLp>) -> Result<()> {
+        let mut partner = ctx.accounts.partner.load_mut()?;
+        partner.partner_lp = ctx.accounts.partner_lp.key();
+        Ok(())
+    }
+
+    /// fund partner the sharing fee in vault lp, lp is valued at current virtual price
+    pub fn fund_partner_lp<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, FundPartnerLp<'info>>,
+        lp_amount: u64,
+        reference: Option<[u8; 32]>,
+    ) -> Result<()> {
+        let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+            .ok()
+            .ok_or(VaultError::MathOverflow)?;
+        let virtual_price = ctx
+            .accounts
+            .vault
+            .get_virtual_price(current_time, ctx.accounts.vault_lp_mint.supply)
+            .ok_or(VaultError::MathOverflow)?;
+        let amount =
+            u64::try_from(lp_to_amount(lp_amount, virtual_price).ok_or(VaultError::MathOverflow)?)
+                .ok()
+                .ok_or(VaultError::MathOverflow)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funder_lp.to_account_info(),
+                    to: ctx.accounts.partner_lp.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            ),
+            lp_amount,
+        )?;
+        // deduct underlying value of lp
+        let payout_receipt_bump = *ctx
+            .bumps
+            .get("payout_receipt")
+            .ok_or(VaultError::InvalidBump)?;
+        settle_partner_fee(
+            &ctx.accounts.partner,
+            &mut ctx.accounts.payout_receipt,
+            ctx.accounts.funder.key(),
+            PayoutType::Lp,
+            amount,
+            reference,
+            payout_receipt_bump,
+        )?;
+
+        emit!(PartnerFundedLp {
+            partner: ctx.accounts.partner.key(),
+            lp_amount,
+            amount,
+            virtual_price,
+        });
+        Ok(())
+    }
+}
+
+/// update liquidity
+#[allow(clippy::too_many_arguments)]
+pub fn update_liquidity_wrapper<'info>(
+    update_liquidity_fn: impl FnOnce() -> Result<()>,
+    vault: &mut Account<'info, Vault>,
+    vault_lp_mint: &mut Account<'info, Mint>,
+    user_lp: &mut Account<'info, TokenAccount>,
+    partner: &AccountLoader<'info, PartnerV2>,
+    user: &AccountLoader<'info, UserV2>,
+    campaign: Option<&mut Account<'info, Campaign>>,
+    policy: FeeAccrualPolicy,
+) -> Result<()> {
+    let partner_key = partner.key();
+    let user_key = user.key();
+    // user signs vault withdrawals, so partner and user are not borrowed during update_liquidity_fn
+    let (accrual, current_time, previous_lp) = {
+        let mut partner = partner.load_mut()?;
+        let mut user = user.load_mut()?;
+
+        // accrue fee
+        let accrual = match get_fee_accrual(vault, vault_lp_mint, &partner, &user) {
+            Ok(accrual) => Some(accrual),
+            Err(reason) => {
+                skip_fee_accrual(partner_key, user_key, reason, policy)?;
+                None
+            }
+        };
+
+        if let Some(accrual) = accrual {
+            let fee = accrual.fee;
+            msg!("fee: {}", fee);
+            emit!(PartnerFee { fee });
+            // acrrure fee for partner, overflow is spilled instead of blocking user
+            let spill = partner.accrue_fee(fee);
+            if spill != FeeSpill::default() {
+                emit!(PartnerFeeSpilled {
+                    partner: partner_key,
+                    fee,
+                    spilled_fee: spill.spilled_fee,
+                    dropped_cumulative_fee: spill.dropped_cumulative_fee,
+                    total_spilled_fee: partner.spilled_fee,
+                });
+            }
+            partner.last_accrual_at = accrual.current_time;
+            // credit slice of partner fee back to user, partner funds it through rebate escrow
+            partner.accrue_rebate(accrual.rebate);
+            user.accrue_rebate(accrual.rebate);
+        }
+
+        // points are accrued with lp held before liquidity changes, also when fee accrual is skipped
+        let current_time = accrual.map(|accrual| accrual.current_time).or_else(|| {
+            Clock::get()
+                .ok()
+                .and_then(|clock| u64::try_from(clock.unix_timestamp).ok())
+        });
+        if let Some(current_time) = current_time {
+            accrue_user_points(
+                partner_key,
+                user_key,
+                &mut user,
+                partner.get_points_multiplier(),
+                current_time,
+            );
+        }
+
+        // users created before partner counters are counted on first touch
+        partner.count_user(&mut user);
+        (accrual, current_time, user.lp_token)
+    };
+    update_liquidity_fn()?;
+
+    // save new user state
+    user_lp.reload()?;
+    let mut partner = partner.load_mut()?;
+    let mut user = user.load_mut()?;
+    let accrual = match accrual {
+        Some(accrual) => {
+            let new_lp = user_lp.amount;
+            let updated = update_atomically(&mut partner, &mut user, |partner, user| {
+                partner.update_liquidity(previous_lp, new_lp, accrual.virtual_price)?;
+                user.update_lifetime_stats(
+                    accrual.yield_earned,
+                    accrual.fee,
+                    new_lp,
+                    accrual.virtual_price,
+                    accrual.current_time,
+                )
+            });
+            if updated.is_none() {
+                skip_fee_accrual(
+                    partner_key,
+                    user_key,
+                    FeeAccrualSkipReason::LifetimeStats,
+                    policy,
+                )?;
+                // lp under management is still tracked without volume and lifetime stats
+                partner.update_total_lp(previous_lp, new_lp);
+                user.record_interaction(accrual.current_time);
+            }
+            accrual
+        }
+        None => {
+            // fee of skipped accrual is not charged, user keeps earning from last virtual price
+            partner.update_total_lp(previous_lp, user_lp.amount);
+            if let Some(current_time) = current_time {
+                user.record_interaction(current_time);
+            }
+            let virtual_price = user.current_virtual_price;
+            user.set_new_state(virtual_price, user_lp.amount);
+            return Ok(());
+        }
+    };
+    if let Some(campaign) = campaign {
+        campaign.record(user_lp.amount.saturating_sub(previous_lp), accrual.fee);
+    }
+    user.set_new_state(accrual.virtual_price, user_lp.amount);
+
+    Ok(())
+}
+
+/// apply update to copies of partner and user, both are written only if the update succeeds
+pub fn update_atomically(
+    partner: &mut PartnerV2,
+    user: &mut UserV2,
+    update: impl FnOnce(&mut PartnerV2, &mut UserV2) -> Option<()>,
+) -> Option<()> {
+    let mut new_partner = *partner;
+    let mut new_user = *user;
+    update(&mut new_partner, &mut new_user)?;
+    *partner = new_partner;
+    *user = new_user;
+    Some(())
+}
+
+/// grow partner account to PARTNER_SPACE, additional rent is paid by payer, system program is only
+/// required when rent must be topped up
+pub fn realloc_partner<'info>(
+    partner: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: Option<&AccountInfo<'info>>,
+) -> Result<()> {
+    if partner.data_len() >= PARTNER_SPACE {
+        return Ok(());
+    }
+
+    let rent_exempt_lamports = Rent::get()?.minimum_balance(PARTNER_SPACE);
+    let lamports = rent_exempt_lamports.saturating_sub(partner.lamports());
+    if lamports > 0 {
+        let system_program = system_program.ok_or(VaultError::MigrationAccountsRequired)?;
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: partner.clone(),
+                },
+            ),
+            lamports,
+        )?;
+    }
+    partner.realloc(PARTNER_SPACE, true)?;
+    Ok(())
+}
+
+/// migrate partner from borsh layout to zero-copy layout in place, partner is resized first if
+/// needed, partner already in zero-copy layout is left untouched
+pub fn migrate_partner_in_place<'info>(
+    partner: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: Option<&AccountInfo<'info>>,
+) -> Result<()> {
+    {
+        let data = partner.try_borrow_data()?;
+        require!(data.len() >= 8, VaultError::InvalidPartnerAccount);
+        if data[..8] == PartnerV2::DISCRIMINATOR {
+            // already migrated
+            return Ok(());
+        }
+        require!(
+            data[..8] == Partner::DISCRIMINATOR,
+            VaultError::InvalidPartnerAccount
+        );
+    }
+    realloc_partner(partner, payer, system_program)?;
+
+    let mut data = partner.try_borrow_mut_data()?;
+    let legacy = Partner::try_deserialize(&mut &data[..])?;
+    write_zero_copy(&mut data, &PartnerV2::from(&legacy))
+}
+
+/// migrate user from borsh layout to zero-copy layout in place, user already in zero-copy layout
+/// is left untouched
+pub fn migrate_user_in_place(user: &AccountInfo<'_>) -> Result<()> {
+    let mut data = user.try_borrow_mut_data()?;
+    require!(data.len() >= 8, VaultError::InvalidUserAccount);
+    if data[..8] == UserV2::DISCRIMINATOR {
+        // already migrated
+        return Ok(());
+    }
+    require!(
+        data[..8] == User::DISCRIMINATOR,
+        VaultError::InvalidUserAccount
+    );
+
+    let legacy = User::try_deserialize(&mut &data[..])?;
+    write_zero_copy(&mut data, &UserV2::from(&legacy))
+}
+
+/// load partner and user of deposit/withdraw, accounts still in borsh layout are migrated in place
+/// first so exits never depend on a separate migration, owner pays the partner resize
+pub fn load_liquidity_accounts<'info>(
+    partner: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    vault: Pubkey,
+    owner: &AccountInfo<'info>,
+    system_program: Option<&AccountInfo<'info>>,
+) -> Result<(
+    AccountLoader<'info, PartnerV2>,
+    AccountLoader<'info, UserV2>,
+)> {
+    migrate_partner_in_place(partner, owner, system_program)?;
+    migrate_user_in_place(user)?;
+
+    let partner_loader = AccountLoader::<PartnerV2>::try_from(partner)?;
+    let user_loader = AccountLoader::<UserV2>::try_from(user)?;
+    {
+        let partner_state = partner_loader.load()?;
+        let user_state = user_loader.load()?;
+        require_keys_eq!(partner_state.vault, vault, ErrorCode::ConstraintHasOne);
+        require_keys_eq!(
+            user_state.partner,
+            partner.key(),
+            ErrorCode::ConstraintHasOne
+        );
+        require_keys_eq!(user_state.owner, owner.key(), ErrorCode::ConstraintHasOne);
+    }
+    Ok((partner_loader, user_loader))
+}
+
+/// overwrite account data with discriminator and zero-copy account, the rest of data is zeroed
+pub fn write_zero_copy<T: ZeroCopy>(data: &mut [u8], account: &T) -> Result<()> {
+    let bytes = bytemuck::bytes_of(account);
+    let len = bytes.len().checked_add(8).ok_or(VaultError::MathOverflow)?;
+    require!(data.len() >= len, ErrorCode::AccountDidNotSerialize);
+    data.fill(0);
+    data[..8].copy_from_slice(&T::DISCRIMINATOR);
+    data[8..len].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// fee accrued on deposit/withdraw, computed before any state is changed
+#[derive(Clone, Copy, Debug)]
+pub struct FeeAccrual {
+    /// current time
+    pub current_time: u64,
+    /// current virtual price
+    pub virtual_price: u64,
+    /// yield earned by user since last accrual
+    pub yield_earned: u128,
+    /// fee accrued for partner
+    pub fee: u64,
+    /// rebate credited to user
+    pub rebate: u64,
+}
+
+/// compute fee accrual of user, returns reason of failure so exits can skip accrual
+pub fn get_fee_accrual(
+    vault: &Vault,
+    vault_lp_mint: &Mint,
+    partner: &PartnerV2,
+    user: &UserV2,
+) -> std::result::Result<FeeAccrual, FeeAccrualSkipReason> {
+    let current_time = Clock::get()
+        .ok()
+        .and_then(|clock| u64::try_from(clock.unix_timestamp).ok())
+        .ok_or(FeeAccrualSkipReason::InvalidTimestamp)?;
+    let virtual_price = vault
+        .get_virtual_price(current_time, vault_lp_mint.supply)
+        .ok_or(FeeAccrualSkipReason::VirtualPrice)?;
+    let yield_earned = user
+        .get_yield_earned(virtual_price)
+        .ok_or(FeeAccrualSkipReason::YieldEarned)?;
+    let fee_ratio = user.get_fee_ratio(partner.fee_ratio);
+    let fee = user
+        .get_fee(virtual_price, fee_ratio)
+        .ok_or(FeeAccrualSkipReason::Fee)?;
+    let rebate = partner
+        .get_rebate(fee)
+        .ok_or(FeeAccrualSkipReason::Rebate)?;
+    Ok(FeeAccrual {
+        current_time,
+        virtual_price,
+        yield_earned,
+        fee,
+        rebate,
+    })
+}
+
+/// fail closed on deposits, on withdrawals emit skipped event so user funds are never trapped
+pub fn skip_fee_accrual(
+    partner: Pubkey,
+    user: Pubkey,
+    reason: FeeAccrualSkipReason,
+    policy: FeeAccrualPolicy,
+) -> Result<()> {
+    match policy {
+        FeeAccrualPolicy::FailClosed => Err(VaultError::MathOverflow.into()),
+        FeeAccrualPolicy::FailOpen => {
+            msg!("fee accrual skipped: {:?}", reason);
+            emit!(FeeAccrualSkipped {
+                user,
+                partner,
+                reason,
+            });
+            Ok(())
+        }
+    }
+}
+
+/// create temporary wsol account of user as user_token, funded with lamports of owner
+pub fn create_temp_wsol(accounts: &DepositWithdrawLiquidity<'_>, lamports: u64) -> Result<()> {
+    let (native_mint, system_program) = match (&accounts.native_mint, &accounts.system_program) {
+        (Some(native_mint), Some(system_program)) => (native_mint, system_program),
+        _ => return Err(VaultError::NativeSolAccountsRequired.into()),
+    };
+    let user_key = accounts.user.key();
+    let (temp_wsol, bump) =
+        Pubkey::find_program_address(&[TEMP_WSOL_PREFIX, user_key.as_ref()], &crate::ID);
+    require_keys_eq!(
+        accounts.user_token.key(),
+        temp_wsol,
+        VaultError::NativeSolAccountsRequired
+    );
+
+    let space = spl_token::state::Account::LEN;
+    let required_lamports = Rent::get()?
+        .minimum_balance(space)
+        .checked_add(lamports)
+        .ok_or(VaultError::MathOverflow)?;
+    let temp_wsol_seeds: &[&[u8]] = &[TEMP_WSOL_PREFIX, user_key.as_ref(), &[bump]];
+    let current_lamports = accounts.user_token.lamports();
+    if current_lamports == 0 {
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: accounts.owner.to_account_info(),
+                    to: accounts.user_token.to_account_info(),
+                },
+                &[temp_wsol_seeds],
+            ),
+            required_lamports,
+            space as u64,
+            &token::ID,
+        )?;
+    } else {
+        // anyone can send lamports to the PDA and make create_account fail, so it is topped up,
+        // allocated and assigned instead, extra lamports are returned to owner on close
+        let top_up = required_lamports.saturating_sub(current_lamports);
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: accounts.owner.to_account_info(),
+                        to: accounts.user_token.to_account_info(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        anchor_lang::system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Allocate {
+                    account_to_allocate: accounts.user_token.to_account_info(),
+                },
+                &[temp_wsol_seeds],
+            ),
+            space as u64,
+        )?;
+        anchor_lang::system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Assign {
+                    account_to_assign: accounts.user_token.to_account_info(),
+                },
+                &[temp_wsol_seeds],
+            ),
+            &token::ID,
+        )?;
+    }
+    // owner is the authority, so owner signature is reused by vault deposit and close
+    token::initialize_account3(CpiContext::new(
+        accounts.token_program.to_account_info(),
+        InitializeAccount3 {
+            account: accounts.user_token.to_account_info(),
+            mint: native_mint.to_account_info(),
+            authority: accounts.owner.to_account_info(),
+        },
+    ))
+}
+
+/// close temporary wsol account, remaining wsol and rent are returned to owner as native SOL
+pub fn close_temp_wsol(accounts: &DepositWithdrawLiquidity<'_>) -> Result<()> {
+    token::close_account(CpiContext::new(
+        accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: accounts.user_token.to_account_info(),
+            destination: accounts.owner.to_account_info(),
+            authority: accounts.owner.to_account_info(),
+        },
+    ))
+}
+
+/// accrue time-weighted points of user and emit event
+pub fn accrue_user_points(
+    partner: Pubkey,
+    user: Pubkey,
+    user_state: &mut UserV2,
+    points_multiplier: u64,
+    current_time: u64,
+) {
+    let points = user_state.accrue_points(current_time, points_multiplier);
+    if points > 0 {
+        emit!(PointsAccrued {
+            user,
+            partner,
+            points,
+            total_points: user_state.points(),
+            timestamp: current_time,
+        });
+    }
+}
+
+/// deduct settled fee from partner outstanding fee and write payout receipt
+pub fn settle_partner_fee<'info>(
+    partner: &AccountLoader<'info, PartnerV2>,
+    payout_receipt: &mut Account<'info, PayoutReceipt>,
+    funder: Pubkey,
+    payout_type: PayoutType,
+    amount: u64,
+    reference: Option<[u8; 32]>,
+    bump: u8,
+) -> Result<()> {
+    let current_time = u64::try_from(Clock::get()?.unix_timestamp)
+        .ok()
+        .ok_or(VaultError::MathOverflow)?;
+
+    let partner_key = partner.key();
+    let mut partner = partner.load_mut()?;
+    let outstanding_fee_before = partner.outstanding_fee;
+    // deduct fee amount, if amount > self.outstanding_fee, then it returns MathOverflow
+    partner.outstanding_fee = outstanding_fee_before
+        .checked_sub(amount)
+        .ok_or(VaultError::MathOverflow)?;
+    // payout made room in outstanding fee for fee spilled on overflow
+    partner.restore_spilled_fee();
+    let index = partner.payout_count;
+    partner.payout_count = index.checked_add(1).ok_or(VaultError::MathOverflow)?;
+
+    payout_receipt.set_inner(PayoutReceipt {
+        partner: partner_key,
+        index,
+        funder,
+        payout_type,
+        amount,
+        timestamp: current_time,
+        outstanding_fee_before,
+        outstanding_fee_after: partner.outstanding_fee,
+        reference,
+        bump,
+    });
+    Ok(())
+}
+
+/// InitPartner struct
+#[derive(Accounts)]
+pub struct InitPartner<'info> {
+    /// Vault account
+    #[account(
+            init,
+            seeds = [
+                vault.key().as_ref(), partner_token.key().as_ref(),
+            ],
+            bump,
+            payer = admin,
+            space = PARTNER_SPACE,
+        )]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK: partner_token mint must be same as native token in vault
+    #[account(constraint = vault.token_mint == partner_token.mint)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin or partner manager address
+    #[account(mut, constraint = has_role(admin.key(), &config, Role::PartnerManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Rent account
+    pub rent: Sysvar<'info, Rent>,
+    /// Token program account
+    pub token_program: Program<'info, Token>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// InitConfig struct
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    /// Config account
+    #[account(
+            init,
+            seeds = [CONFIG_PREFIX],
+            bump,
+            payer = admin,
+            space = Config::SPACE,
+        )]
+    pub config: Box<Account<'info, Config>>,
+
+    /// Admin address
+    #[account(mut, constraint = admin.key() == get_admin_address())]
+    pub admin: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+}
+
+/// UpdateConfig struct
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// Config account
+    #[account(mut, seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// Admin address
+    #[account(constraint = admin.key() == get_admin_address())]
+    pub admin: Signer<'info>,
+}
+
+/// PausePartner struct
+#[derive(Accounts)]
+pub struct PausePartner<'info> {
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+
+    /// Admin or pauser address, only admin can unpause
+    #[account(constraint = has_role(admin.key(), &config, Role::Pauser) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// SetUsersBackfilled struct
+#[derive(Accounts)]
+pub struct SetUsersBackfilled<'info> {
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+
+    /// Admin or partner manager address
+    #[account(constraint = has_role(admin.key(), &config, Role::PartnerManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// ClosePartner struct
+#[derive(Accounts)]
+pub struct ClosePartner<'info> {
+    /// CHECK:
+    #[account(mut, close = admin)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// Partner group, only required when partner is linked to a group
+    #[account(mut)]
+    pub partner_group: Option<Box<Account<'info, PartnerGroup>>>,
+
+    /// Admin or partner manager address, receives the rent back
+    #[account(mut, constraint = has_role(admin.key(), &config, Role::PartnerManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+    /// CHECK: rebate escrow of partner, may not be created, closed with its balance returned to partner token
+    #[account(
+            mut,
+            seeds = [
+                REBATE_ESCROW_PREFIX, partner.key().as_ref(),
+            ],
+            bump,
+        )]
+    pub rebate_escrow: UncheckedAccount<'info>,
+    /// Partner token account, receives balance of rebate escrow
+    #[account(mut, address = partner.load()?.partner_token)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// Token program account
+    pub token_program: Program<'info, Token>,
+}
+
+/// UpdateFeeRatio struct
+#[derive(Accounts)]
+pub struct UpdateFeeRatio<'info> {
+    /// Vault account
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+
+    /// Admin or fee manager address
+    #[account(constraint = has_role(admin.key(), &config, Role::FeeManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// InitPartnerGroup struct
+#[derive(Accounts)]
+pub struct InitPartnerGroup<'info> {
+    /// Partner group account
+    #[account(
+            init,
+            seeds = [
+                PARTNER_GROUP_PREFIX, partner_wallet.key().as_ref(),
+            ],
+            bump,
+            payer = admin,
+            space = 200 // data + buffer,
+        )]
+    pub partner_group: Box<Account<'info, PartnerGroup>>,
+    /// CHECK: Partner wallet, owner of partner token in every vault
+    pub partner_wallet: UncheckedAccount<'info>,
+
+    /// Admin or partner manager address
+    #[account(mut, constraint = has_role(admin.key(), &config, Role::PartnerManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// LinkPartnerGroup struct
+#[derive(Accounts)]
+pub struct LinkPartnerGroup<'info> {
+    /// Partner group account
+    #[account(mut)]
+    pub partner_group: Box<Account<'info, PartnerGroup>>,
+    /// CHECK:
+    #[account(mut, has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, must be owned by partner wallet of the group
+    #[account(constraint = partner_token.owner == partner_group.partner_wallet @ VaultError::InvalidOwner)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin or partner manager address
+    #[account(constraint = has_role(admin.key(), &config, Role::PartnerManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// UpdateGroupFeeRatio struct
+#[derive(Accounts)]
+pub struct UpdateGroupFeeRatio<'info> {
+    /// Partner group account
+    #[account(mut)]
+    pub partner_group: Box<Account<'info, PartnerGroup>>,
+
+    /// Admin or fee manager address
+    #[account(constraint = has_role(admin.key(), &config, Role::FeeManager) @ VaultError::InvalidAuthority)]
+    pub admin: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// UpdatePartnerSetting struct
+#[derive(Accounts)]
+pub struct UpdatePartnerSetting<'info> {
+    /// CHECK:
+    #[account(mut, has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, its owner is the partner wallet
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin or partner wallet
+    #[account(
+        constraint = authority.key() == get_admin_address()
+            || authority.key() == partner_token.owner @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+}
+
+/// InitPartnerProfile struct
+#[derive(Accounts)]
+pub struct InitPartnerProfile<'info> {
+    /// Partner profile account
+    #[account(
+            init,
+            seeds = [
+                PARTNER_PROFILE_PREFIX, partner_wallet.key().as_ref(),
+            ],
+            bump,
+            payer = partner_wallet,
+            space = PartnerProfile::SPACE,
+        )]
+    pub partner_profile: Box<Account<'info, PartnerProfile>>,
+
+    /// Partner wallet, owner of partner token in every vault
+    #[account(mut)]
+    pub partner_wallet: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+}
+
+/// UpdatePartnerProfile struct
+#[derive(Accounts)]
+pub struct UpdatePartnerProfile<'info> {
+    /// Partner profile account
+    #[account(mut, has_one = partner_wallet)]
+    pub partner_profile: Box<Account<'info, PartnerProfile>>,
+
+    /// Partner wallet
+    pub partner_wallet: Signer<'info>,
+}
+
+/// UpdateUserFeeRatio struct
+#[derive(Accounts)]
+pub struct UpdateUserFeeRatio<'info> {
+    /// User account
+    #[account(mut, has_one = partner)]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, its owner is the partner wallet
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin or partner wallet
+    #[account(
+        constraint = authority.key() == get_admin_address()
+            || authority.key() == partner_token.owner @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+}
+
+/// InitUser struct
+#[derive(Accounts)]
+pub struct InitUser<'info> {
+    /// User account
+    #[account(
+            init,
+            seeds = [
+                partner.key().as_ref(), owner.key().as_ref(),
+            ],
+            bump,
+            payer = owner,
+            space = 200 // data + buffer,
+        )]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+
+    /// signer address
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Rent account
+    pub rent: Sysvar<'info, Rent>,
+
+    /// partner token, only required when partner gates onboarding
+    #[account(constraint = partner_token.key() == partner.load()?.partner_token)]
+    pub partner_token: Option<Box<Account<'info, TokenAccount>>>,
+    /// partner wallet, only required when partner gates onboarding
+    pub partner_authority: Option<Signer<'info>>,
+}
+
+/// InitUser struct
+#[derive(Accounts)]
+pub struct InitUserPermissionless<'info> {
+    /// User account
+    #[account(
+            init,
+            seeds = [
+                partner.key().as_ref(), owner.key().as_ref(),
+            ],
+            bump,
+            payer = payer,
+            space = 200 // data + buffer,
+        )]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+
+    /// CHECK: Owner of the wallet
+    pub owner: UncheckedAccount<'info>,
+
+    /// signer address
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+}
+
+/// CloseUser struct
+#[derive(Accounts)]
+pub struct CloseUser<'info> {
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// User account, rebate that is not claimed is forfeited
+    #[account(
+        mut,
+        has_one = partner,
+        has_one = owner,
+        close = owner
+    )]
+    pub user: AccountLoader<'info, UserV2>,
+    /// user_lp, must be empty before closing
+    #[account(
+        mut,
+        constraint = user_lp.owner == user.key(),
+        constraint = user_lp.amount == 0 @ VaultError::UserLpNotEmpty
+    )]
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    /// owner of the user account, receives the rent back
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Token program account
+    pub token_program: Program<'info, Token>,
+}
+
+/// CountUser struct
+#[derive(Accounts)]
+pub struct CountUser<'info> {
+    /// CHECK:
+    #[account(mut)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// User account
+    #[account(mut, has_one = partner)]
+    pub user: AccountLoader<'info, UserV2>,
+}
+
+/// RegisterReferralCode struct
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct RegisterReferralCode<'info> {
+    /// Referral code account
+    #[account(
+            init,
+            seeds = [
+                REFERRAL_CODE_PREFIX, code.as_bytes(),
+            ],
+            bump,
+            payer = authority,
+            space = ReferralCode::SPACE,
+        )]
+    pub referral_code: Box<Account<'info, ReferralCode>>,
+
+    /// CHECK: Partner wallet that referral code points to
+    pub partner_wallet: UncheckedAccount<'info>,
+    /// any partner of the wallet, proves that the wallet is a partner
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, must be owned by partner wallet
+    #[account(constraint = partner_token.owner == partner_wallet.key() @ VaultError::InvalidOwner)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin, partner manager or partner wallet, pays the rent
+    #[account(
+        mut,
+        constraint = has_role(authority.key(), &config, Role::PartnerManager)
+            || authority.key() == partner_wallet.key() @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Config account, only required when signer is not super admin or partner wallet
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// ReassignReferralCode struct
+#[derive(Accounts)]
+pub struct ReassignReferralCode<'info> {
+    /// Referral code account
+    #[account(mut)]
+    pub referral_code: Box<Account<'info, ReferralCode>>,
+
+    /// CHECK: Partner wallet that referral code points to
+    pub partner_wallet: UncheckedAccount<'info>,
+    /// any partner of the wallet, proves that the wallet is a partner
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, must be owned by partner wallet
+    #[account(constraint = partner_token.owner == partner_wallet.key() @ VaultError::InvalidOwner)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+
+    /// Admin, partner manager or current partner wallet of the code
+    #[account(
+        constraint = has_role(authority.key(), &config, Role::PartnerManager)
+            || authority.key() == referral_code.partner_wallet @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// CloseReferralCode struct
+#[derive(Accounts)]
+pub struct CloseReferralCode<'info> {
+    /// Referral code account
+    #[account(mut, close = rent_receiver)]
+    pub referral_code: Box<Account<'info, ReferralCode>>,
+    /// CHECK: payer of the code, receives the rent back
+    #[account(mut, address = referral_code.payer)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    /// Admin, partner manager or partner wallet of the code
+    #[account(
+        constraint = has_role(authority.key(), &config, Role::PartnerManager)
+            || authority.key() == referral_code.partner_wallet @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+    /// Config account, only required when signer is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// InitUserWithReferralCode struct
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct InitUserWithReferralCode<'info> {
+    /// User account
+    #[account(
+            init,
+            seeds = [
+                partner.key().as_ref(), owner.key().as_ref(),
+            ],
+            bump,
+            payer = owner,
+            space = 200 // data + buffer,
+        )]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    #[account(mut, has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, must be owned by the wallet that referral code points to
+    #[account(constraint = partner_token.owner == referral_code.partner_wallet @ VaultError::InvalidReferralCode)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// Referral code account
+    #[account(
+            seeds = [
+                REFERRAL_CODE_PREFIX, code.as_bytes(),
+            ],
+            bump = referral_code.bump,
+        )]
+    pub referral_code: Box<Account<'info, ReferralCode>>,
+
+    /// signer address
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+}
+
+/// Need to check whether we can convert to unchecked account
+#[derive(Accounts)]
+pub struct DepositWithdrawLiquidity<'info> {
+    /// CHECK: partner in either layout, migrated in place and checked in load_liquidity_accounts
+    #[account(mut, owner = crate::ID)]
+    pub partner: UncheckedAccount<'info>,
+    /// CHECK: user in either layout, migrated in place and checked in load_liquidity_accounts
+    #[account(mut, owner = crate::ID)]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK:
+    pub vault_program: Program<'info, MercurialVault>,
+    /// CHECK:
+    #[account(mut)]
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK:
+    #[account(mut)]
+    pub token_vault: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    /// CHECK:
+    #[account(mut)]
+    pub user_token: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut, constraint = user_lp.owner == user.key())] //mint to account of user PDA
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    /// owner of the user account, pays resize of partner in borsh layout
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+    /// Campaign account, only required when deposit is tagged with campaign
+    #[account(mut, has_one = partner)]
+    pub campaign: Option<Box<Account<'info, Campaign>>>,
+    /// Native mint, only required for native SOL, user_token must be temporary wsol account of user
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: Option<Box<Account<'info, Mint>>>,
+    /// System program account, only required for native SOL or partner in borsh layout
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Accounts for deposit on behalf of beneficiary, stable interface for cpi callers
+#[derive(Accounts)]
+pub struct DepositFor<'info> {
+    /// CHECK:
+    #[account(mut, has_one = vault)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// User account of beneficiary, created by init_user_permissionless, its owner is the beneficiary
+    #[account(mut, has_one = partner)]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    pub vault_program: Program<'info, MercurialVault>,
+    /// CHECK:
+    #[account(mut)]
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK:
+    #[account(mut)]
+    pub token_vault: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    /// CHECK: token account of depositor, transfer is authorized by depositor
+    #[account(mut)]
+    pub depositor_token: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut, constraint = user_lp.owner == user.key())] //mint to account of user PDA
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    /// depositor, can be a PDA of calling program
+    pub depositor: Signer<'info>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for basket deposit, accounts of each leg are passed as remaining accounts
+#[derive(Accounts)]
+pub struct DepositBasket<'info> {
+    /// CHECK:
+    pub vault_program: Program<'info, MercurialVault>,
+    /// owner of user accounts of every leg
+    pub owner: Signer<'info>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for withdraw directly from a strategy
+#[derive(Accounts)]
+pub struct WithdrawDirectlyFromStrategy<'info> {
+    /// CHECK: partner in either layout, migrated in place and checked in load_liquidity_accounts
+    #[account(mut, owner = crate::ID)]
+    pub partner: UncheckedAccount<'info>,
+    /// CHECK: user in either layout, migrated in place and checked in load_liquidity_accounts
+    #[account(mut, owner = crate::ID)]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK:
+    pub vault_program: Program<'info, MercurialVault>,
+
+    /// vault
+    #[account(mut)]
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK:
+    #[account(mut)]
+    pub strategy: UncheckedAccount<'info>,
+
+    /// CHECK:: Reserve account
+    #[account(mut)]
+    pub reserve: UncheckedAccount<'info>,
+
+    /// CHECK:: Strategy program
+    pub strategy_program: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub collateral_vault: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub token_vault: UncheckedAccount<'info>,
+    /// lp_mint
+    #[account(mut)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    /// CHECK:
+    #[account(mut)]
+    pub fee_vault: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub user_token: UncheckedAccount<'info>,
+    /// user_lp
+    #[account(mut, constraint = user_lp.owner == user.key())] //unmint from account of user PDA
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    /// user, pays resize of partner in borsh layout
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// token_program
+    pub token_program: Program<'info, Token>,
+    /// System program account, only required for partner in borsh layout, must be passed
+    /// (or replaced by the program id) before the strategy remaining accounts
+    pub system_program: Option<Program<'info, System>>,
+}
+
+/// Need to check whether we can convert to unchecked account
+#[derive(Accounts)]
+pub struct FundPartner<'info> {
+    /// CHECK:
+    #[account(mut, has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    #[account(mut)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    #[account(mut, constraint = funder_token.key() != partner_token.key() @ VaultError::WrongFunderToken)]
+    pub funder_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    #[account(mut, constraint = is_funder(funder.key(), &config) @ VaultError::UnauthorizedFunder)]
+    pub funder: Signer<'info>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+    /// Payout receipt account
+    #[account(
+            init,
+            seeds = [
+                PAYOUT_RECEIPT_PREFIX, partner.key().as_ref(), partner.load()?.payout_count.to_le_bytes().as_ref(),
+            ],
+            bump,
+            payer = funder,
+            space = 200 // data + buffer,
+        )]
+    pub payout_receipt: Box<Account<'info, PayoutReceipt>>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Config account, only required when funder is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// FundPartnerVesting struct
+#[derive(Accounts)]
+pub struct FundPartnerVesting<'info> {
+    /// CHECK:
+    #[account(mut, has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// token mint of partner token
+    #[account(address = partner_token.mint)]
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// Partner vesting account
+    #[account(
+            init,
+            seeds = [
+                PARTNER_VESTING_PREFIX, partner.key().as_ref(), partner.load()?.vesting_count.to_le_bytes().as_ref(),
+            ],
+            bump,
+            payer = funder,
+            space = 200 // data + buffer,
+        )]
+    pub partner_vesting: Box<Account<'info, PartnerVesting>>,
+    /// Escrow token account holding unvested fee
+    #[account(
+            init,
             seeds = [
-                vault.key().as_ref(), partner_token.key().as_ref(),
+                VESTING_ESCROW_PREFIX, partner_vesting.key().as_ref(),
             ],
             bump,
-            payer = admin,
+            payer = funder,
+            token::mint = token_mint,
+            token::authority = partner_vesting,
+        )]
+    pub escrow: Box<Account<'info, TokenAccount>>,
+    /// Payout receipt account
+    #[account(
+            init,
+            seeds = [
+                PAYOUT_RECEIPT_PREFIX, partner.key().as_ref(), partner.load()?.payout_count.to_le_bytes().as_ref(),
+            ],
+            bump,
+            payer = funder,
             space = 200 // data + buffer,
         )]
-    pub partner: Box<Account<'info, Partner>>,
+    pub payout_receipt: Box<Account<'info, PayoutReceipt>>,
     /// CHECK:
-    pub vault: Box<Account<'info, Vault>>,
-    /// CHECK: partner_token mint must be same as native token in vault
-    #[account(constraint = vault.token_mint == partner_token.mint)]
-    pub partner_token: Box<Account<'info, TokenAccount>>,
-
-    /// Admin address
-    #[account(mut, constraint = admin.key() == get_admin_address())]
-    pub admin: Signer<'info>,
-
+    #[account(mut, constraint = funder_token.key() != partner_token.key() @ VaultError::WrongFunderToken)]
+    pub funder_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    #[account(mut, constraint = is_funder(funder.key(), &config) @ VaultError::UnauthorizedFunder)]
+    pub funder: Signer<'info>,
     /// System program account
     pub system_program: Program<'info, System>,
     /// Rent account
     pub rent: Sysvar<'info, Rent>,
-    /// Token program account
+    /// CHECK:
     pub token_program: Program<'info, Token>,
+    /// Config account, only required when funder is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
 }
 
-/// UpdateFeeRatio struct
+/// ClaimVested struct
 #[derive(Accounts)]
-pub struct UpdateFeeRatio<'info> {
-    /// Vault account
+pub struct ClaimVested<'info> {
+    /// CHECK:
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    #[account(mut)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// Partner vesting account
+    #[account(mut, has_one = partner, has_one = escrow)]
+    pub partner_vesting: Box<Account<'info, PartnerVesting>>,
+    /// Escrow token account holding unvested fee
     #[account(mut)]
-    pub partner: Box<Account<'info, Partner>>,
+    pub escrow: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+}
 
-    /// Admin address
-    #[account(constraint = admin.key() == get_admin_address())]
-    pub admin: Signer<'info>,
+/// GetUserPendingFee struct
+#[derive(Accounts)]
+pub struct GetUserPendingFee<'info> {
+    /// CHECK:
+    #[account(has_one = vault)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    #[account(has_one = partner)]
+    pub user: AccountLoader<'info, UserV2>,
+    /// CHECK:
+    pub vault: Box<Account<'info, Vault>>,
+    /// lp_mint
+    #[account(address = vault.lp_mint)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
 }
 
-/// InitUser struct
+/// GetPartnerSummary struct
 #[derive(Accounts)]
-pub struct InitUser<'info> {
-    /// User account
+pub struct GetPartnerSummary<'info> {
+    /// CHECK:
+    #[account(has_one = vault)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    pub vault: Box<Account<'info, Vault>>,
+    /// lp_mint
+    #[account(address = vault.lp_mint)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+}
+
+/// ResizePartner struct
+#[derive(Accounts)]
+pub struct ResizePartner<'info> {
+    /// CHECK: partner account in borsh layout, discriminator is checked in handler
+    #[account(mut, owner = crate::ID)]
+    pub partner: UncheckedAccount<'info>,
+
+    /// payer of additional rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+}
+
+/// MigrateUser struct
+#[derive(Accounts)]
+pub struct MigrateUser<'info> {
+    /// CHECK: user account in borsh layout, discriminator is checked in handler
+    #[account(mut, owner = crate::ID)]
+    pub user: UncheckedAccount<'info>,
+}
+
+/// SetPartnerLp struct
+#[derive(Accounts)]
+pub struct SetPartnerLp<'info> {
+    /// CHECK:
+    #[account(mut, has_one = partner_token, has_one = vault)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, its owner is the partner wallet
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    pub vault: Box<Account<'info, Vault>>,
+    /// partner lp, must be vault lp token account owned by partner wallet
+    #[account(
+        constraint = partner_lp.mint == vault.lp_mint @ VaultError::InvalidPartnerLp,
+        constraint = partner_lp.owner == partner_token.owner @ VaultError::InvalidPartnerLp
+    )]
+    pub partner_lp: Box<Account<'info, TokenAccount>>,
+
+    /// Admin or partner wallet
+    #[account(
+        constraint = authority.key() == get_admin_address()
+            || authority.key() == partner_token.owner @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+}
+
+/// FundPartnerLp struct
+#[derive(Accounts)]
+pub struct FundPartnerLp<'info> {
+    /// CHECK:
+    #[account(mut, has_one = partner_lp, has_one = vault)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    #[account(mut)]
+    pub partner_lp: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    pub vault: Box<Account<'info, Vault>>,
+    /// lp_mint
+    #[account(address = vault.lp_mint)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    /// CHECK:
+    #[account(mut, constraint = funder_lp.key() != partner_lp.key() @ VaultError::WrongFunderToken)]
+    pub funder_lp: Box<Account<'info, TokenAccount>>,
+    /// CHECK:
+    #[account(mut, constraint = is_funder(funder.key(), &config) @ VaultError::UnauthorizedFunder)]
+    pub funder: Signer<'info>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
+    /// Payout receipt account
     #[account(
             init,
             seeds = [
-                partner.key().as_ref(), owner.key().as_ref(),
+                PAYOUT_RECEIPT_PREFIX, partner.key().as_ref(), partner.load()?.payout_count.to_le_bytes().as_ref(),
             ],
             bump,
-            payer = owner,
+            payer = funder,
             space = 200 // data + buffer,
         )]
-    pub user: Box<Account<'info, User>>,
+    pub payout_receipt: Box<Account<'info, PayoutReceipt>>,
+    /// System program account
+    pub system_program: Program<'info, System>,
+    /// Config account, only required when funder is not super admin
+    #[account(seeds = [CONFIG_PREFIX], bump = config.bump)]
+    pub config: Option<Box<Account<'info, Config>>>,
+}
+
+/// InitRebateEscrow struct
+#[derive(Accounts)]
+pub struct InitRebateEscrow<'info> {
     /// CHECK:
-    pub partner: Box<Account<'info, Partner>>,
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// CHECK:
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// token mint of partner token
+    #[account(address = partner_token.mint)]
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// Rebate escrow token account, owned by partner PDA
+    #[account(
+            init,
+            seeds = [
+                REBATE_ESCROW_PREFIX, partner.key().as_ref(),
+            ],
+            bump,
+            payer = payer,
+            token::mint = token_mint,
+            token::authority = partner,
+        )]
+    pub rebate_escrow: Box<Account<'info, TokenAccount>>,
 
-    /// signer address
+    /// payer of escrow rent
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub payer: Signer<'info>,
     /// System program account
     pub system_program: Program<'info, System>,
     /// Rent account
     pub rent: Sysvar<'info, Rent>,
+    /// CHECK:
+    pub token_program: Program<'info, Token>,
 }
 
-/// InitUser struct
+/// InitCampaign struct
 #[derive(Accounts)]
-pub struct InitUserPermissionless<'info> {
-    /// User account
+#[instruction(campaign_id: u32)]
+pub struct InitCampaign<'info> {
+    /// Campaign account
     #[account(
             init,
             seeds = [
-                partner.key().as_ref(), owner.key().as_ref(),
+                CAMPAIGN_PREFIX, partner.key().as_ref(), campaign_id.to_le_bytes().as_ref(),
             ],
             bump,
-            payer = payer,
+            payer = authority,
             space = 200 // data + buffer,
         )]
-    pub user: Box<Account<'info, User>>,
+    pub campaign: Box<Account<'info, Campaign>>,
     /// CHECK:
-    pub partner: Box<Account<'info, Partner>>,
-
-    /// CHECK: Owner of the wallet
-    pub owner: UncheckedAccount<'info>,
+    #[account(has_one = partner_token)]
+    pub partner: AccountLoader<'info, PartnerV2>,
+    /// partner token, its owner is the partner wallet
+    pub partner_token: Box<Account<'info, TokenAccount>>,
 
-    /// signer address
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    /// Admin or partner wallet
+    #[account(
+        mut,
+        constraint = authority.key() == get_admin_address()
+            || authority.key() == partner_token.owner @ VaultError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
     /// System program account
     pub system_program: Program<'info, System>,
 }
 
-/// Need to check whether we can convert to unchecked account
+/// RefreshPoints struct
 #[derive(Accounts)]
-pub struct DepositWithdrawLiquidity<'info> {
-    /// CHECK:
-    #[account(mut, has_one = vault)]
-    pub partner: Box<Account<'info, Partner>>,
-    /// CHECK:
-    #[account(mut, has_one = partner, has_one = owner)]
-    pub user: Box<Account<'info, User>>,
-    /// CHECK:
-    pub vault_program: Program<'info, MercurialVault>,
+pub struct RefreshPoints<'info> {
     /// CHECK:
-    #[account(mut)]
-    pub vault: Box<Account<'info, Vault>>,
+    pub partner: AccountLoader<'info, PartnerV2>,
     /// CHECK:
-    #[account(mut)]
-    pub token_vault: UncheckedAccount<'info>,
+    #[account(mut, has_one = partner)]
+    pub user: AccountLoader<'info, UserV2>,
+}
+
+/// ClaimRebate struct
+#[derive(Accounts)]
+pub struct ClaimRebate<'info> {
     /// CHECK:
-    #[account(mut)]
-    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        has_one = vault,
+        has_one = partner_token,
+        seeds = [vault.key().as_ref(), partner_token.key().as_ref()],
+        bump,
+    )]
+    pub partner: AccountLoader<'info, PartnerV2>,
     /// CHECK:
-    #[account(mut)]
-    pub user_token: UncheckedAccount<'info>,
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK: only used as seed of partner
+    pub vault: UncheckedAccount<'info>,
     /// CHECK:
-    #[account(mut, constraint = user_lp.owner == user.key())] //mint to account of user PDA
-    pub user_lp: Box<Account<'info, TokenAccount>>,
+    #[account(mut, has_one = partner, has_one = owner)]
+    pub user: AccountLoader<'info, UserV2>,
+    /// Rebate escrow token account
+    #[account(
+            mut,
+            seeds = [
+                REBATE_ESCROW_PREFIX, partner.key().as_ref(),
+            ],
+            bump,
+        )]
+    pub rebate_escrow: Box<Account<'info, TokenAccount>>,
     /// CHECK:
+    #[account(mut, constraint = owner_token.mint == rebate_escrow.mint)]
+    pub owner_token: Box<Account<'info, TokenAccount>>,
+    /// owner of the user account
     pub owner: Signer<'info>,
     /// CHECK:
     pub token_program: Program<'info, Token>,
 }
 
-/// Accounts for withdraw directly from a strategy
-#[derive(Accounts)]
-pub struct WithdrawDirectlyFromStrategy<'info> {
-    /// CHECK:
-    #[account(mut, has_one = vault)]
-    pub partner: Box<Account<'info, Partner>>,
-    /// CHECK:
-    #[account(mut, has_one = partner, has_one = owner)]
-    pub user: Box<Account<'info, User>>,
-    /// CHECK:
-    pub vault_program: Program<'info, MercurialVault>,
+/// Partner struct, legacy borsh layout of deployed partners, only read by migrate_partner,
+/// fields added after it live in PartnerV2 only
+#[account]
+#[derive(Default, Debug)]
+pub struct Partner {
+    /// partner token address, which is used to get fee later (fee is in native token)
+    pub partner_token: Pubkey, // 32
+    /// vault address that partner integrates
+    pub vault: Pubkey, // 32
+    /// total fee that partner get, but haven't sent yet
+    pub outstanding_fee: u64, // 8
+    /// fee ratio partner get in performance fee
+    pub fee_ratio: u64, // 8
+    // cumulative fee partner get from start
+    pub cumulative_fee: u128, // 16
+}
+
+/// Partner struct, zero-copy layout, u128 are stored as little-endian bytes so the layout has
+/// no padding on any target, offsets in comments exclude the discriminator
+#[account(zero_copy)]
+#[derive(Default, Debug)]
+pub struct PartnerV2 {
+    /// cumulative fee partner get from start
+    cumulative_fee: [u8; 16], // 0
+    /// total deposited amount in native token, valued at virtual price
+    total_deposited: [u8; 16], // 16
+    /// total withdrawn amount in native token, valued at virtual price
+    total_withdrawn: [u8; 16], // 32
+    /// partner token address, which is used to get fee later (fee is in native token)
+    pub partner_token: Pubkey, // 48
+    /// vault address that partner integrates
+    pub vault: Pubkey, // 80
+    /// partner group that partner is linked to, default pubkey if not linked
+    pub group: Pubkey, // 112
+    /// vault lp token account to receive fee in lp, default pubkey if not set
+    pub partner_lp: Pubkey, // 144
+    /// total fee that partner get, but haven't sent yet
+    pub outstanding_fee: u64, // 176
+    /// fee ratio partner get in performance fee
+    pub fee_ratio: u64, // 184
+    /// number of users attributed to the partner
+    pub user_count: u64, // 192
+    /// total lp token held by users of the partner
+    pub total_lp: u64, // 200
+    /// last time fee was accrued for the partner
+    pub last_accrual_at: u64, // 208
+    /// number of vesting created for partner, used as seed of next vesting
+    pub vesting_count: u64, // 216
+    /// number of payout made to partner, used as seed of next payout receipt
+    pub payout_count: u64, // 224
+    /// ratio of accrued fee credited back to users as rebate
+    pub rebate_ratio: u64, // 232
+    /// total rebate credited to users, but haven't claimed yet
+    pub outstanding_rebate: u64, // 240
+    /// multiplier of points accrued by users, 10_000 is 1x, 0 is the default multiplier
+    pub points_multiplier: u64, // 248
+    /// fee that overflowed outstanding fee, moved back to outstanding fee after payout
+    pub spilled_fee: u64, // 256
+    /// 1 if partner wallet must co-sign init_user
+    pub require_partner_signature: u8, // 264
+    /// 1 if deposits through the partner are paused, withdrawals are always allowed
+    pub paused: u8, // 265
+    /// 1 if every user is counted in user_count and total_lp
+    pub users_backfilled: u8, // 266
+    _padding: [u8; 5],    // 267
+    _reserved: [u64; 15], // 272, 392 bytes in total
+}
+
+impl From<&Partner> for PartnerV2 {
+    /// fields added after the legacy layout start from default, users are counted afterward
+    fn from(partner: &Partner) -> Self {
+        PartnerV2 {
+            cumulative_fee: partner.cumulative_fee.to_le_bytes(),
+            partner_token: partner.partner_token,
+            vault: partner.vault,
+            outstanding_fee: partner.outstanding_fee,
+            fee_ratio: partner.fee_ratio,
+            ..Default::default()
+        }
+    }
+}
+
+impl PartnerV2 {
+    /// byte offset of group in account data, used by memcmp filters of clients
+    pub const GROUP_OFFSET: usize = 8 // discriminator
+        + 16 // cumulative_fee
+        + 16 // total_deposited
+        + 16 // total_withdrawn
+        + 32 // partner_token
+        + 32; // vault
+
+    /// get cumulative fee partner get from start
+    pub fn cumulative_fee(&self) -> u128 {
+        u128::from_le_bytes(self.cumulative_fee)
+    }
+
+    /// get total deposited amount in native token
+    pub fn total_deposited(&self) -> u128 {
+        u128::from_le_bytes(self.total_deposited)
+    }
+
+    /// get total withdrawn amount in native token
+    pub fn total_withdrawn(&self) -> u128 {
+        u128::from_le_bytes(self.total_withdrawn)
+    }
+
+    /// accrue fee, fee that doesn't fit outstanding fee is moved to spilled fee, cumulative fee saturates
+    pub fn accrue_fee(&mut self, fee: u64) -> FeeSpill {
+        let accrued = fee.min(u64::MAX.saturating_sub(self.outstanding_fee));
+        self.outstanding_fee = self.outstanding_fee.saturating_add(accrued);
+        let spilled_fee = fee.saturating_sub(accrued);
+        self.spilled_fee = self.spilled_fee.saturating_add(spilled_fee);
+
+        let cumulative_fee = self.cumulative_fee();
+        let buffer = u128::MAX.saturating_sub(cumulative_fee);
+        let dropped_cumulative_fee =
+            u64::try_from(u128::from(fee).saturating_sub(buffer)).unwrap_or(fee);
+        self.cumulative_fee = cumulative_fee.saturating_add(fee.into()).to_le_bytes();
+
+        FeeSpill {
+            spilled_fee,
+            dropped_cumulative_fee,
+        }
+    }
+
+    /// move spilled fee back to outstanding fee as far as it fits
+    pub fn restore_spilled_fee(&mut self) {
+        let restored = self
+            .spilled_fee
+            .min(u64::MAX.saturating_sub(self.outstanding_fee));
+        self.outstanding_fee = self.outstanding_fee.saturating_add(restored);
+        self.spilled_fee = self.spilled_fee.saturating_sub(restored);
+    }
+
+    /// get rebate credited to user from accrued fee
+    pub fn get_rebate(&self, fee: u64) -> Option<u64> {
+        u64::try_from(
+            u128::from(fee)
+                .checked_mul(self.rebate_ratio.into())?
+                .checked_div(FEE_DENOMINATOR)?,
+        )
+        .ok()
+    }
+
+    /// add rebate credited to user to outstanding rebate
+    pub fn accrue_rebate(&mut self, rebate: u64) {
+        self.outstanding_rebate = self.outstanding_rebate.saturating_add(rebate);
+    }
+
+    /// add new user to partner
+    pub fn add_user(&mut self) {
+        self.user_count = self.user_count.saturating_add(1);
+    }
+
+    /// remove user from partner
+    pub fn remove_user(&mut self, lp_token: u64) {
+        self.user_count = self.user_count.saturating_sub(1);
+        self.total_lp = self.total_lp.saturating_sub(lp_token);
+    }
+
+    /// get multiplier of points, partners created before points were introduced have 0
+    pub fn get_points_multiplier(&self) -> u64 {
+        if self.points_multiplier == 0 {
+            DEFAULT_POINTS_MULTIPLIER
+        } else {
+            self.points_multiplier
+        }
+    }
+
+    /// count user created before partner counters, with lp it holds, only once
+    pub fn count_user(&mut self, user: &mut UserV2) {
+        if user.counted() {
+            return;
+        }
+        self.add_user();
+        self.total_lp = self.total_lp.saturating_add(user.lp_token);
+        user.set_flag(UserV2::FLAG_COUNTED, true);
+    }
+
+    /// update lp under management after user liquidity changed, user must be counted
+    pub fn update_total_lp(&mut self, previous_lp: u64, new_lp: u64) {
+        self.total_lp = self
+            .total_lp
+            .saturating_sub(previous_lp)
+            .saturating_add(new_lp);
+    }
+
+    /// update lp under management and deposited/withdrawn volume after user liquidity changed
+    pub fn update_liquidity(
+        &mut self,
+        previous_lp: u64,
+        new_lp: u64,
+        virtual_price: u64,
+    ) -> Option<()> {
+        self.update_total_lp(previous_lp, new_lp);
+
+        if new_lp >= previous_lp {
+            let amount = lp_to_amount(new_lp.checked_sub(previous_lp)?, virtual_price)?;
+            self.total_deposited = self.total_deposited().saturating_add(amount).to_le_bytes();
+        } else {
+            let amount = lp_to_amount(previous_lp.checked_sub(new_lp)?, virtual_price)?;
+            self.total_withdrawn = self.total_withdrawn().saturating_add(amount).to_le_bytes();
+        }
+        Some(())
+    }
+}
+
+/// fee that didn't fit partner accounting on accrual
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSpill {
+    /// fee that overflowed outstanding fee, moved to spilled fee
+    pub spilled_fee: u64,
+    /// fee that couldn't be added to saturated cumulative fee
+    pub dropped_cumulative_fee: u64,
+}
+
+/// convert lp amount to native token amount at virtual price
+pub fn lp_to_amount(lp_amount: u64, virtual_price: u64) -> Option<u128> {
+    u128::from(lp_amount)
+        .checked_mul(u128::from(virtual_price))?
+        .checked_div(PRICE_PRECISION)
+}
+
+/// Campaign struct, aggregates deposits tagged with campaign within one partner
+#[account]
+#[derive(Default, Debug)]
+pub struct Campaign {
+    /// partner address
+    pub partner: Pubkey, // 32
+    /// campaign id, unique per partner
+    pub campaign_id: u32, // 4
+    /// total lp minted by deposits tagged with campaign
+    pub lp_inflow: u64, // 8
+    /// total fee generated by users whose last-touch is the campaign
+    pub fee_generated: u64, // 8
+    /// number of deposits tagged with campaign
+    pub deposit_count: u64, // 8
+    /// campaign bump
+    pub bump: u8, // 1
+}
+
+impl Campaign {
+    /// record lp inflow and fee of one deposit/withdraw
+    pub fn record(&mut self, lp_inflow: u64, fee: u64) {
+        if lp_inflow > 0 {
+            self.lp_inflow = self.lp_inflow.saturating_add(lp_inflow);
+            self.deposit_count = self.deposit_count.saturating_add(1);
+        }
+        self.fee_generated = self.fee_generated.saturating_add(fee);
+    }
+}
+
+/// PartnerVesting struct, fee funded to partner and released linearly
+#[account]
+#[derive(Default, Debug)]
+pub struct PartnerVesting {
+    /// partner address
+    pub partner: Pubkey, // 32
+    /// escrow token account holding unvested fee
+    pub escrow: Pubkey, // 32
+    /// index of vesting of the partner
+    pub index: u64, // 8
+    /// total amount funded
+    pub total_amount: u64, // 8
+    /// amount released to partner token
+    pub claimed_amount: u64, // 8
+    /// time vesting started
+    pub start_at: u64, // 8
+    /// nothing is released before start_at + cliff_duration
+    pub cliff_duration: u64, // 8
+    /// total amount is released at start_at + duration
+    pub duration: u64, // 8
+    /// partner vesting bump
+    pub bump: u8, // 1
+}
+
+impl PartnerVesting {
+    /// get amount released linearly until current time
+    pub fn get_vested_amount(&self, current_time: u64) -> Option<u64> {
+        let elapsed = current_time.saturating_sub(self.start_at);
+        if elapsed < self.cliff_duration {
+            return Some(0);
+        }
+        if elapsed >= self.duration {
+            return Some(self.total_amount);
+        }
+        let vested_amount = u128::from(self.total_amount)
+            .checked_mul(elapsed.into())?
+            .checked_div(self.duration.into())?;
+        u64::try_from(vested_amount).ok()
+    }
+
+    /// get vested amount that has not been claimed
+    pub fn get_claimable_amount(&self, current_time: u64) -> Option<u64> {
+        self.get_vested_amount(current_time)?
+            .checked_sub(self.claimed_amount)
+    }
+}
+
+/// Config struct, global settings of the program
+#[account]
+#[derive(Default, Debug)]
+pub struct Config {
+    /// treasury, allowed to fund partners
+    pub treasury: Pubkey, // 32
+    /// other accounts allowed to fund partners
+    pub funders: Vec<Pubkey>, // 4 + 32 * MAX_FUNDERS
+    /// config bump
+    pub bump: u8, // 1
+    /// partner manager, allowed to init and close partners
+    pub partner_manager: Pubkey, // 32
+    /// fee manager, allowed to update fee ratio
+    pub fee_manager: Pubkey, // 32
+    /// pauser, allowed to pause partners
+    pub pauser: Pubkey, // 32
+}
+
+/// Role enum, roles assigned by super admin in config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// init and close partners
+    PartnerManager,
+    /// update fee ratio
+    FeeManager,
+    /// fund partners
+    Treasury,
+    /// pause partners
+    Pauser,
+}
+
+/// check whether signer is super admin or has the role in config
+pub fn has_role(signer: Pubkey, config: &Option<Box<Account<'_, Config>>>, role: Role) -> bool {
+    signer == get_admin_address() || matches!(config, Some(config) if config.has_role(signer, role))
+}
+
+/// check whether funder is super admin or allowed to fund partners in config
+pub fn is_funder(funder: Pubkey, config: &Option<Box<Account<'_, Config>>>) -> bool {
+    funder == get_admin_address() || matches!(config, Some(config) if config.is_funder(funder))
+}
+
+impl Config {
+    /// check whether account has the role, unassigned roles match nobody
+    pub fn has_role(&self, signer: Pubkey, role: Role) -> bool {
+        let role_account = match role {
+            Role::PartnerManager => self.partner_manager,
+            Role::FeeManager => self.fee_manager,
+            Role::Treasury => self.treasury,
+            Role::Pauser => self.pauser,
+        };
+        role_account != Pubkey::default() && signer == role_account
+    }
+
+    /// account space including discriminator
+    pub const SPACE: usize = 1000; // data + buffer
 
-    /// vault
-    #[account(mut)]
-    pub vault: Box<Account<'info, Vault>>,
-    /// CHECK:
-    #[account(mut)]
-    pub strategy: UncheckedAccount<'info>,
+    /// check whether account is allowed to fund partners, admin is always allowed
+    pub fn is_funder(&self, funder: Pubkey) -> bool {
+        funder == get_admin_address()
+            || self.has_role(funder, Role::Treasury)
+            || self.funders.contains(&funder)
+    }
+}
 
-    /// CHECK:: Reserve account
-    #[account(mut)]
-    pub reserve: UncheckedAccount<'info>,
+/// FeeAccrualPolicy enum, what happens when fee can't be accrued on deposit/withdraw
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeAccrualPolicy {
+    /// revert, used by deposits
+    FailClosed,
+    /// skip fee accrual and emit FeeAccrualSkipped, used by withdrawals
+    FailOpen,
+}
 
-    /// CHECK:: Strategy program
-    pub strategy_program: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub collateral_vault: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub token_vault: UncheckedAccount<'info>,
-    /// lp_mint
-    #[account(mut)]
-    pub vault_lp_mint: Box<Account<'info, Mint>>,
-    /// CHECK:
-    #[account(mut)]
-    pub fee_vault: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub user_token: UncheckedAccount<'info>,
-    /// user_lp
-    #[account(mut, constraint = user_lp.owner == user.key())] //unmint from account of user PDA
-    pub user_lp: Box<Account<'info, TokenAccount>>,
-    /// user
-    pub owner: Signer<'info>,
-    /// token_program
-    pub token_program: Program<'info, Token>,
+/// FeeAccrualSkipReason enum, step of fee accrual that failed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeAccrualSkipReason {
+    /// clock timestamp is unavailable or negative
+    InvalidTimestamp,
+    /// virtual price can't be computed, for example lp supply is zero
+    VirtualPrice,
+    /// yield earned by user overflows
+    YieldEarned,
+    /// fee of user overflows
+    Fee,
+    /// rebate of user overflows
+    Rebate,
+    /// partner volume or user lifetime stats overflow after liquidity changed
+    LifetimeStats,
 }
 
-/// Need to check whether we can convert to unchecked account
-#[derive(Accounts)]
-pub struct FundPartner<'info> {
-    /// CHECK:
-    #[account(mut, has_one = partner_token)]
-    pub partner: Box<Account<'info, Partner>>,
-    /// CHECK:
-    #[account(mut)]
-    pub partner_token: Box<Account<'info, TokenAccount>>,
-    /// CHECK:
-    #[account(mut, constraint = funder_token.key() != partner_token.key() @ VaultError::WrongFunderToken)]
-    pub funder_token: Box<Account<'info, TokenAccount>>,
-    /// CHECK:
-    pub funder: Signer<'info>,
-    /// CHECK:
-    pub token_program: Program<'info, Token>,
+/// PayoutType enum
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayoutType {
+    /// fee is sent as native token to partner token
+    #[default]
+    Token,
+    /// fee is sent as vault lp to partner lp
+    Lp,
+    /// fee is sent to vesting escrow
+    Vesting,
 }
 
-/// Partner struct
+/// PayoutReceipt struct, written for every funding of partner
 #[account]
-#[derive(Debug)]
-pub struct Partner {
-    /// partner token address, which is used to get fee later (fee is in native token)
-    pub partner_token: Pubkey, // 32
-    /// vault address that partner integrates
-    pub vault: Pubkey, // 32
-    /// total fee that partner get, but haven't sent yet
-    pub outstanding_fee: u64, // 8
-    /// fee ratio partner get in performance fee
+#[derive(Default, Debug)]
+pub struct PayoutReceipt {
+    /// partner address
+    pub partner: Pubkey, // 32
+    /// index of payout of the partner
+    pub index: u64, // 8
+    /// funder address
+    pub funder: Pubkey, // 32
+    /// how fee is paid
+    pub payout_type: PayoutType, // 1
+    /// amount deducted from outstanding fee, in native token
+    pub amount: u64, // 8
+    /// time of the payout
+    pub timestamp: u64, // 8
+    /// outstanding fee before the payout
+    pub outstanding_fee_before: u64, // 8
+    /// outstanding fee after the payout
+    pub outstanding_fee_after: u64, // 8
+    /// optional invoice or reference hash
+    pub reference: Option<[u8; 32]>, // 1 + 32
+    /// payout receipt bump
+    pub bump: u8, // 1
+}
+
+/// PartnerGroup struct, links partners of the same wallet across vaults
+#[account]
+#[derive(Default, Debug)]
+pub struct PartnerGroup {
+    /// partner wallet address
+    pub partner_wallet: Pubkey, // 32
+    /// fee ratio applied to every partner of the group on last group update
     pub fee_ratio: u64, // 8
-    // cumulative fee partner get from start
-    pub cumulative_fee: u128, // 16
+    /// number of partners linked to the group
+    pub partner_count: u64, // 8
+    /// partner group bump
+    pub bump: u8, // 1
 }
 
-impl Partner {
-    /// accrue fee
-    pub fn accrue_fee(&mut self, fee: u64) -> Option<()> {
-        self.outstanding_fee = self.outstanding_fee.checked_add(fee)?;
-        let max = u128::MAX;
-        let buffer = max - self.cumulative_fee;
-        let fee: u128 = fee.into();
-        if buffer >= fee {
-            // only add if we have enough room
-            self.cumulative_fee = self.cumulative_fee.checked_add(fee)?;
-        }
-        Some(())
+/// PartnerProfile struct, shared by every partner whose partner token is owned by partner wallet
+#[account]
+#[derive(Default, Debug)]
+pub struct PartnerProfile {
+    /// partner wallet address
+    pub partner_wallet: Pubkey, // 32
+    /// display name
+    pub name: String, // 4 + 32
+    /// website uri
+    pub website_uri: String, // 4 + 128
+    /// logo uri
+    pub logo_uri: String, // 4 + 128
+    /// contact, e.g. email or telegram handle
+    pub contact: String, // 4 + 64
+    /// partner profile bump
+    pub bump: u8, // 1
+}
+
+impl PartnerProfile {
+    /// account space including discriminator
+    pub const SPACE: usize = 8
+        + 32
+        + 4
+        + MAX_PROFILE_NAME_LEN
+        + 4
+        + MAX_PROFILE_URI_LEN
+        + 4
+        + MAX_PROFILE_URI_LEN
+        + 4
+        + MAX_PROFILE_CONTACT_LEN
+        + 1;
+
+    /// update profile fields
+    pub fn update(
+        &mut self,
+        name: String,
+        website_uri: String,
+        logo_uri: String,
+        contact: String,
+    ) -> Result<()> {
+        require!(
+            name.len() <= MAX_PROFILE_NAME_LEN
+                && website_uri.len() <= MAX_PROFILE_URI_LEN
+                && logo_uri.len() <= MAX_PROFILE_URI_LEN
+                && contact.len() <= MAX_PROFILE_CONTACT_LEN,
+            VaultError::ProfileFieldTooLong
+        );
+        self.name = name;
+        self.website_uri = website_uri;
+        self.logo_uri = logo_uri;
+        self.contact = contact;
+        Ok(())
+    }
+}
+
+/// ReferralCode struct, maps a human-readable code to partner wallet
+#[account]
+#[derive(Default, Debug)]
+pub struct ReferralCode {
+    /// referral code, ascii alphanumeric, '-' or '_'
+    pub code: String, // 4 + 16
+    /// partner wallet that code points to
+    pub partner_wallet: Pubkey, // 32
+    /// payer of the code rent, receives the rent back on close
+    pub payer: Pubkey, // 32
+    /// referral code bump
+    pub bump: u8, // 1
+}
+
+impl ReferralCode {
+    /// account space including discriminator
+    pub const SPACE: usize = 8 + 4 + MAX_REFERRAL_CODE_LEN + 32 + 32 + 1;
+
+    /// check whether code is a short ascii code
+    pub fn is_valid_code(code: &str) -> bool {
+        !code.is_empty()
+            && code.len() <= MAX_REFERRAL_CODE_LEN
+            && code
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
     }
 }
 
-/// User struct
+/// User struct, legacy borsh layout of deployed users, only read by migrate_user, fields added
+/// after it live in UserV2 only
 #[account]
 #[derive(Default, Debug)]
 pub struct User {
@@ -527,17 +3317,237 @@ pub struct User {
 }
 
 impl User {
-    /// get fee per user
-    pub fn get_fee(&mut self, virtual_price: u64, fee_ratio: u64) -> Option<u64> {
+    /// byte offset of partner in account data, used by memcmp filters of clients
+    pub const PARTNER_OFFSET: usize = 8 // discriminator
+        + 32; // owner
+}
+
+/// User struct, zero-copy layout, u128 are stored as little-endian bytes so the layout has
+/// no padding on any target, options are stored as value and flag, offsets in comments exclude
+/// the discriminator
+#[account(zero_copy)]
+#[derive(Default, Debug)]
+pub struct UserV2 {
+    /// points accrued as lp token multiplied by seconds held
+    points: [u8; 16], // 0
+    owner: Pubkey, // 16
+    /// partner address, each user can integrate with more partners
+    partner: Pubkey, // 48
+    /// current virtual price
+    current_virtual_price: u64, // 80
+    /// lp_token that user holds
+    lp_token: u64, // 88
+    /// cumulative yield that user earned through the partner
+    cumulative_yield: u64, // 96
+    /// cumulative fee that user generated for the partner
+    cumulative_fee: u64, // 104
+    /// total deposited amount in native token, valued at virtual price
+    total_deposited: u64, // 112
+    /// total withdrawn amount in native token, valued at virtual price
+    total_withdrawn: u64, // 120
+    /// first time user deposited or withdrew through the partner
+    first_interaction_at: u64, // 128
+    /// last time user deposited or withdrew through the partner
+    last_interaction_at: u64, // 136
+    /// fee ratio that overrides partner fee ratio, set if FLAG_FEE_RATIO_OVERRIDE is set
+    fee_ratio_override: u64, // 144
+    /// rebate credited by the partner, but haven't claimed yet
+    outstanding_rebate: u64, // 152
+    /// last time points were accrued
+    last_points_update_at: u64, // 160
+    /// first campaign that user deposited with, set if FLAG_FIRST_CAMPAIGN is set
+    first_campaign_id: u32, // 168
+    /// last campaign that user deposited with, set if FLAG_LAST_CAMPAIGN is set
+    last_campaign_id: u32, // 172
+    /// user bump
+    bump: u8, // 176
+    /// FLAG_* bits
+    flags: u8, // 177
+    _padding: [u8; 14], // 178, 192 bytes in total
+}
+
+impl From<&User> for UserV2 {
+    /// fields added after the legacy layout start from default, so user is not counted yet
+    fn from(user: &User) -> Self {
+        UserV2 {
+            owner: user.owner,
+            partner: user.partner,
+            current_virtual_price: user.current_virtual_price,
+            lp_token: user.lp_token,
+            bump: user.bump,
+            ..Default::default()
+        }
+    }
+}
+
+impl UserV2 {
+    /// byte offset of partner in account data, used by memcmp filters of clients
+    pub const PARTNER_OFFSET: usize = 8 // discriminator
+        + 16 // points
+        + 32; // owner
+
+    /// user is counted in user_count and total_lp of partner, unset for users created before
+    /// the counters until they are touched or counted with count_user
+    pub const FLAG_COUNTED: u8 = 1;
+    /// fee_ratio_override is set
+    pub const FLAG_FEE_RATIO_OVERRIDE: u8 = 1 << 1;
+    /// first_campaign_id is set
+    pub const FLAG_FIRST_CAMPAIGN: u8 = 1 << 2;
+    /// last_campaign_id is set
+    pub const FLAG_LAST_CAMPAIGN: u8 = 1 << 3;
+
+    fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// get owner of the user account
+    pub fn owner(&self) -> Pubkey {
+        self.owner
+    }
+
+    /// get partner of the user account
+    pub fn partner(&self) -> Pubkey {
+        self.partner
+    }
+
+    /// get lp token that user holds
+    pub fn lp_token(&self) -> u64 {
+        self.lp_token
+    }
+
+    /// get cumulative yield that user earned through the partner
+    pub fn cumulative_yield(&self) -> u64 {
+        self.cumulative_yield
+    }
+
+    /// get cumulative fee that user generated for the partner
+    pub fn cumulative_fee(&self) -> u64 {
+        self.cumulative_fee
+    }
+
+    /// get total deposited amount in native token
+    pub fn total_deposited(&self) -> u64 {
+        self.total_deposited
+    }
+
+    /// get total withdrawn amount in native token
+    pub fn total_withdrawn(&self) -> u64 {
+        self.total_withdrawn
+    }
+
+    /// get fee ratio override, set by admin or partner
+    pub fn fee_ratio_override(&self) -> Option<u64> {
+        self.has_flag(Self::FLAG_FEE_RATIO_OVERRIDE)
+            .then_some(self.fee_ratio_override)
+    }
+
+    /// set fee ratio override, None removes the override
+    pub fn set_fee_ratio_override(&mut self, fee_ratio: Option<u64>) {
+        self.fee_ratio_override = fee_ratio.unwrap_or_default();
+        self.set_flag(Self::FLAG_FEE_RATIO_OVERRIDE, fee_ratio.is_some());
+    }
+
+    /// get whether user is counted in partner counters
+    pub fn counted(&self) -> bool {
+        self.has_flag(Self::FLAG_COUNTED)
+    }
+
+    /// get first-touch campaign of user
+    pub fn first_campaign_id(&self) -> Option<u32> {
+        self.has_flag(Self::FLAG_FIRST_CAMPAIGN)
+            .then_some(self.first_campaign_id)
+    }
+
+    /// get last-touch campaign of user
+    pub fn last_campaign_id(&self) -> Option<u32> {
+        self.has_flag(Self::FLAG_LAST_CAMPAIGN)
+            .then_some(self.last_campaign_id)
+    }
+
+    /// record campaign that user deposited with
+    pub fn touch_campaign(&mut self, campaign_id: u32) {
+        if !self.has_flag(Self::FLAG_FIRST_CAMPAIGN) {
+            self.first_campaign_id = campaign_id;
+            self.set_flag(Self::FLAG_FIRST_CAMPAIGN, true);
+        }
+        self.last_campaign_id = campaign_id;
+        self.set_flag(Self::FLAG_LAST_CAMPAIGN, true);
+    }
+
+    /// get points accrued by user
+    pub fn points(&self) -> u128 {
+        u128::from_le_bytes(self.points)
+    }
+
+    /// get last time points were accrued
+    pub fn last_points_update_at(&self) -> u64 {
+        self.last_points_update_at
+    }
+
+    /// accrue points for lp token held since last update, points never block liquidity changes
+    pub fn accrue_points(&mut self, current_time: u64, points_multiplier: u64) -> u128 {
+        let elapsed = if self.last_points_update_at == 0 {
+            // start accruing from first update
+            0
+        } else {
+            current_time.saturating_sub(self.last_points_update_at)
+        };
+        self.last_points_update_at = current_time;
+
+        let points = u128::from(self.lp_token)
+            .saturating_mul(elapsed.into())
+            .saturating_mul(points_multiplier.into())
+            .checked_div(POINTS_MULTIPLIER_DENOMINATOR)
+            .unwrap_or(0);
+        self.points = self.points().saturating_add(points).to_le_bytes();
+        points
+    }
+
+    /// get rebate that user can claim
+    pub fn outstanding_rebate(&self) -> u64 {
+        self.outstanding_rebate
+    }
+
+    /// credit rebate to user
+    pub fn accrue_rebate(&mut self, rebate: u64) {
+        self.outstanding_rebate = self.outstanding_rebate.saturating_add(rebate);
+    }
+
+    /// deduct claimed rebate
+    pub fn claim_rebate(&mut self, amount: u64) -> Option<()> {
+        self.outstanding_rebate = self.outstanding_rebate.checked_sub(amount)?;
+        Some(())
+    }
+
+    /// get fee ratio applied to user, override is preferred over partner fee ratio
+    pub fn get_fee_ratio(&self, partner_fee_ratio: u64) -> u64 {
+        self.fee_ratio_override().unwrap_or(partner_fee_ratio)
+    }
+
+    /// get yield that user earned since last update
+    pub fn get_yield_earned(&self, virtual_price: u64) -> Option<u128> {
         if virtual_price <= self.current_virtual_price {
-            // if virtual price is reduced, then no fee is accrued
+            // if virtual price is reduced, then no yield is earned
             return Some(0);
         }
-        let yield_earned = u128::from(self.lp_token)
+        u128::from(self.lp_token)
             .checked_mul(u128::from(
                 virtual_price.checked_sub(self.current_virtual_price)?,
             ))?
-            .checked_div(PRICE_PRECISION)?;
+            .checked_div(PRICE_PRECISION)
+    }
+
+    /// get fee per user
+    pub fn get_fee(&self, virtual_price: u64, fee_ratio: u64) -> Option<u64> {
+        let yield_earned = self.get_yield_earned(virtual_price)?;
 
         let performance_fee_by_vault = yield_earned
             .checked_mul(PERFORMANCE_FEE_NUMERATOR)?
@@ -558,6 +3568,41 @@ impl User {
         self.current_virtual_price = virtual_price;
         self.lp_token = lp_token;
     }
+
+    /// update lifetime yield, fee and volume, must be called before set_new_state
+    pub fn update_lifetime_stats(
+        &mut self,
+        yield_earned: u128,
+        fee: u64,
+        new_lp: u64,
+        virtual_price: u64,
+        current_time: u64,
+    ) -> Option<()> {
+        let yield_earned = u64::try_from(yield_earned).unwrap_or(u64::MAX);
+        self.cumulative_yield = self.cumulative_yield.saturating_add(yield_earned);
+        self.cumulative_fee = self.cumulative_fee.saturating_add(fee);
+
+        if new_lp >= self.lp_token {
+            let amount = lp_to_amount(new_lp.checked_sub(self.lp_token)?, virtual_price)?;
+            let amount = u64::try_from(amount).unwrap_or(u64::MAX);
+            self.total_deposited = self.total_deposited.saturating_add(amount);
+        } else {
+            let amount = lp_to_amount(self.lp_token.checked_sub(new_lp)?, virtual_price)?;
+            let amount = u64::try_from(amount).unwrap_or(u64::MAX);
+            self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
+        }
+
+        self.record_interaction(current_time);
+        Some(())
+    }
+
+    /// record deposit or withdrawal time
+    pub fn record_interaction(&mut self, current_time: u64) {
+        if self.first_interaction_at == 0 {
+            self.first_interaction_at = current_time;
+        }
+        self.last_interaction_at = current_time;
+    }
 }
 
 /// VaultError struct
@@ -581,6 +3626,94 @@ pub enum VaultError {
 
     #[msg("Funder token account must be different from partner token account")]
     WrongFunderToken,
+
+    /// UserLpNotEmpty
+    #[msg("User lp token account must be empty")]
+    UserLpNotEmpty,
+
+    /// InvalidAuthority
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+
+    /// PartnerSignatureRequired
+    #[msg("Partner signature is required to init user")]
+    PartnerSignatureRequired,
+
+    /// ProfileFieldTooLong
+    #[msg("Partner profile field is too long")]
+    ProfileFieldTooLong,
+
+    /// InvalidReferralCode
+    #[msg("Invalid referral code")]
+    InvalidReferralCode,
+
+    /// PartnerAlreadyInGroup
+    #[msg("Partner is already linked to another group")]
+    PartnerAlreadyInGroup,
+
+    /// PartnerNotInGroup
+    #[msg("Partner is not linked to the group")]
+    PartnerNotInGroup,
+
+    /// InvalidPartnerAccount
+    #[msg("Invalid partner account")]
+    InvalidPartnerAccount,
+
+    /// InvalidPartnerLp
+    #[msg("Partner lp must be vault lp token account owned by partner wallet")]
+    InvalidPartnerLp,
+
+    /// InvalidVestingSchedule
+    #[msg("Cliff duration must not exceed vesting duration")]
+    InvalidVestingSchedule,
+
+    /// TooManyFunders
+    #[msg("Too many funders")]
+    TooManyFunders,
+
+    /// UnauthorizedFunder
+    #[msg("Funder is not allowed to fund partner")]
+    UnauthorizedFunder,
+
+    /// PartnerPaused
+    #[msg("Partner is paused")]
+    PartnerPaused,
+
+    /// PartnerNotEmpty
+    #[msg("Partner still has users or outstanding fee")]
+    PartnerNotEmpty,
+
+    /// InvalidCampaign
+    #[msg("Invalid campaign")]
+    InvalidCampaign,
+
+    /// InvalidBasket
+    #[msg("Invalid basket accounts")]
+    InvalidBasket,
+
+    /// NativeSolAccountsRequired
+    #[msg("Native mint, system program and temporary wsol account are required for native SOL")]
+    NativeSolAccountsRequired,
+
+    /// PartnerGroupIncomplete
+    #[msg("Every partner of the group must be passed once")]
+    PartnerGroupIncomplete,
+
+    /// PartnerUsersNotBackfilled
+    #[msg("Users created before partner counters must be counted before closing the partner")]
+    PartnerUsersNotBackfilled,
+
+    /// InvalidPointsMultiplier
+    #[msg("Points multiplier is above the maximum")]
+    InvalidPointsMultiplier,
+
+    /// InvalidUserAccount
+    #[msg("Invalid user account")]
+    InvalidUserAccount,
+
+    /// MigrationAccountsRequired
+    #[msg("System program is required to resize a partner in borsh layout")]
+    MigrationAccountsRequired,
 }
 
 #[event]
@@ -588,3 +3721,368 @@ pub enum VaultError {
 pub struct PartnerFee {
     fee: u64,
 }
+
+#[event]
+/// FeeAccrualSkipped struct
+pub struct FeeAccrualSkipped {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// step of fee accrual that failed
+    pub reason: FeeAccrualSkipReason,
+}
+
+#[event]
+/// PartnerFeeSpilled struct
+pub struct PartnerFeeSpilled {
+    /// partner address
+    pub partner: Pubkey,
+    /// fee accrued
+    pub fee: u64,
+    /// fee that overflowed outstanding fee
+    pub spilled_fee: u64,
+    /// fee that couldn't be added to saturated cumulative fee
+    pub dropped_cumulative_fee: u64,
+    /// total spilled fee of partner
+    pub total_spilled_fee: u64,
+}
+
+#[event]
+/// UserPendingFee struct
+pub struct UserPendingFee {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// current virtual price
+    pub virtual_price: u64,
+    /// lp token that user holds
+    pub lp_token: u64,
+    /// position of user in native token, valued at virtual price
+    pub position_amount: u128,
+    /// yield earned since last update
+    pub pending_yield: u128,
+    /// fee that would be accrued for partner now
+    pub pending_fee: u64,
+    /// rebate that user could claim after pending fee is accrued
+    pub outstanding_rebate: u64,
+}
+
+#[event]
+/// PartnerSummary struct
+pub struct PartnerSummary {
+    /// partner address
+    pub partner: Pubkey,
+    /// current virtual price
+    pub virtual_price: u64,
+    /// fee ratio of partner
+    pub fee_ratio: u64,
+    /// number of users attributed to the partner
+    pub user_count: u64,
+    /// total lp token held by users of the partner
+    pub total_lp: u64,
+    /// total lp in native token, valued at virtual price
+    pub total_amount: u128,
+    /// total fee that partner get, but haven't sent yet
+    pub outstanding_fee: u64,
+    /// cumulative fee partner get from start
+    pub cumulative_fee: u128,
+    /// fee that would be accrued for users passed as remaining accounts
+    pub pending_fee: u64,
+    /// number of users passed as remaining accounts
+    pub user_pending_count: u64,
+}
+
+#[event]
+/// UserDeposit struct
+pub struct UserDeposit {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// campaign that deposit is tagged with
+    pub campaign_id: Option<u32>,
+    /// token amount deposited
+    pub token_amount: u64,
+    /// lp amount minted to user
+    pub lp_amount: u64,
+}
+
+#[event]
+/// PointsAccrued struct
+pub struct PointsAccrued {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// points accrued since last update
+    pub points: u128,
+    /// total points of user
+    pub total_points: u128,
+    /// time of update
+    pub timestamp: u64,
+}
+
+#[event]
+/// RebateClaimed struct
+pub struct RebateClaimed {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// rebate amount sent to user
+    pub amount: u64,
+}
+
+#[event]
+/// RebateForfeited struct
+pub struct RebateForfeited {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// rebate that was not claimed when user was closed
+    pub amount: u64,
+}
+
+#[event]
+/// UserFeeRatio struct
+pub struct UserFeeRatio {
+    /// user address
+    pub user: Pubkey,
+    /// partner address
+    pub partner: Pubkey,
+    /// fee ratio override, None if partner fee ratio is applied
+    pub fee_ratio: Option<u64>,
+}
+
+#[event]
+/// PartnerFundedLp struct
+pub struct PartnerFundedLp {
+    /// partner address
+    pub partner: Pubkey,
+    /// lp amount sent to partner
+    pub lp_amount: u64,
+    /// native token amount deducted from outstanding fee
+    pub amount: u64,
+    /// virtual price used for conversion
+    pub virtual_price: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memcmp_offsets() -> Result<()> {
+        let group = Pubkey::new_unique();
+        let partner_key = Pubkey::new_unique();
+        let user = User {
+            partner: partner_key,
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        user.try_serialize(&mut data)?;
+        assert_eq!(
+            &data[User::PARTNER_OFFSET..User::PARTNER_OFFSET + 32],
+            partner_key.as_ref()
+        );
+
+        let mut data = vec![0; PARTNER_SPACE];
+        write_zero_copy(
+            &mut data,
+            &PartnerV2 {
+                group,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            &data[PartnerV2::GROUP_OFFSET..PartnerV2::GROUP_OFFSET + 32],
+            group.as_ref()
+        );
+
+        let mut data = vec![0; 200];
+        write_zero_copy(
+            &mut data,
+            &UserV2 {
+                partner: partner_key,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            &data[UserV2::PARTNER_OFFSET..UserV2::PARTNER_OFFSET + 32],
+            partner_key.as_ref()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_copy_layout() {
+        // layout is identical on host and bpf, where u128 is 8 bytes aligned
+        assert_eq!(std::mem::align_of::<PartnerV2>(), 8);
+        assert_eq!(std::mem::align_of::<UserV2>(), 8);
+        assert_eq!(8 + std::mem::size_of::<PartnerV2>(), PARTNER_SPACE);
+        assert_eq!(8 + std::mem::size_of::<UserV2>(), 200);
+    }
+
+    #[test]
+    fn test_migrate_from_borsh() -> Result<()> {
+        let vault = Pubkey::new_unique();
+        let legacy = Partner {
+            vault,
+            outstanding_fee: 3,
+            fee_ratio: DEFAULT_FEE_RATIO,
+            cumulative_fee: u128::MAX,
+            ..Default::default()
+        };
+        // deployed partners are 200 bytes, resized before migration
+        let mut data = vec![0; 200];
+        legacy.try_serialize(&mut data.as_mut_slice())?;
+        data.resize(PARTNER_SPACE, 0);
+        let legacy = Partner::try_deserialize(&mut data.as_slice())?;
+        write_zero_copy(&mut data, &PartnerV2::from(&legacy))?;
+        let partner = PartnerV2::try_deserialize(&mut data.as_slice())?;
+        assert_eq!(partner.vault, vault);
+        assert_eq!(partner.outstanding_fee, 3);
+        assert_eq!(partner.fee_ratio, DEFAULT_FEE_RATIO);
+        assert_eq!(partner.cumulative_fee(), u128::MAX);
+        assert_eq!(partner.group, Pubkey::default());
+        assert_eq!(partner.user_count, 0);
+        assert_eq!(partner.users_backfilled, 0);
+        assert_eq!(partner.get_points_multiplier(), DEFAULT_POINTS_MULTIPLIER);
+
+        let owner = Pubkey::new_unique();
+        let legacy = User {
+            owner,
+            current_virtual_price: 2,
+            lp_token: 5,
+            bump: 255,
+            ..Default::default()
+        };
+        // deployed users are 200 bytes, migrated without resize
+        let mut data = vec![0; 200];
+        legacy.try_serialize(&mut data.as_mut_slice())?;
+        let legacy = User::try_deserialize(&mut data.as_slice())?;
+        write_zero_copy(&mut data, &UserV2::from(&legacy))?;
+        let user = UserV2::try_deserialize(&mut data.as_slice())?;
+        assert_eq!(user.owner(), owner);
+        assert_eq!(user.current_virtual_price, 2);
+        assert_eq!(user.lp_token(), 5);
+        assert_eq!(user.bump, 255);
+        assert_eq!(user.fee_ratio_override(), None);
+        assert_eq!(user.last_campaign_id(), None);
+        assert_eq!(user.points(), 0);
+        assert!(!user.counted());
+        Ok(())
+    }
+
+    #[test]
+    fn test_accrue_fee_spill() {
+        let mut partner = PartnerV2 {
+            outstanding_fee: u64::MAX - 10,
+            cumulative_fee: (u128::MAX - 4).to_le_bytes(),
+            ..Default::default()
+        };
+        assert_eq!(
+            partner.accrue_fee(15),
+            FeeSpill {
+                spilled_fee: 5,
+                dropped_cumulative_fee: 11,
+            }
+        );
+        assert_eq!(partner.outstanding_fee, u64::MAX);
+        assert_eq!(partner.spilled_fee, 5);
+        assert_eq!(partner.cumulative_fee(), u128::MAX);
+
+        // nothing is restored while outstanding fee is saturated
+        partner.restore_spilled_fee();
+        assert_eq!(partner.outstanding_fee, u64::MAX);
+        assert_eq!(partner.spilled_fee, 5);
+
+        // payout makes room for part of spilled fee
+        partner.outstanding_fee = u64::MAX - 3;
+        partner.restore_spilled_fee();
+        assert_eq!(partner.outstanding_fee, u64::MAX);
+        assert_eq!(partner.spilled_fee, 2);
+
+        partner.outstanding_fee = 0;
+        partner.restore_spilled_fee();
+        assert_eq!(partner.outstanding_fee, 2);
+        assert_eq!(partner.spilled_fee, 0);
+    }
+
+    #[test]
+    fn test_vested_amount_boundaries() {
+        let vesting = PartnerVesting {
+            total_amount: 1_000,
+            claimed_amount: 100,
+            start_at: 1_000,
+            cliff_duration: 100,
+            duration: 400,
+            ..Default::default()
+        };
+        // nothing is released before start or cliff
+        assert_eq!(vesting.get_vested_amount(0), Some(0));
+        assert_eq!(vesting.get_vested_amount(1_099), Some(0));
+        // amount accrued since start is released at cliff
+        assert_eq!(vesting.get_vested_amount(1_100), Some(250));
+        assert_eq!(vesting.get_vested_amount(1_399), Some(997));
+        assert_eq!(vesting.get_vested_amount(1_400), Some(1_000));
+        assert_eq!(vesting.get_vested_amount(u64::MAX), Some(1_000));
+        assert_eq!(vesting.get_claimable_amount(1_400), Some(900));
+
+        // cliff equal to duration releases everything at once
+        let vesting = PartnerVesting {
+            total_amount: 1_000,
+            cliff_duration: 400,
+            duration: 400,
+            ..Default::default()
+        };
+        assert_eq!(vesting.get_vested_amount(399), Some(0));
+        assert_eq!(vesting.get_vested_amount(400), Some(1_000));
+    }
+
+    #[test]
+    fn test_update_atomically() {
+        let mut partner = PartnerV2 {
+            total_lp: 100,
+            ..Default::default()
+        };
+        let mut user = UserV2 {
+            lp_token: 100,
+            ..Default::default()
+        };
+
+        // user update fails after partner is updated, neither is written
+        let updated = update_atomically(&mut partner, &mut user, |partner, _user| {
+            partner.update_liquidity(100, 300, PRICE_PRECISION as u64)?;
+            None
+        });
+        assert_eq!(updated, None);
+        assert_eq!(partner.total_lp, 100);
+        assert_eq!(partner.total_deposited(), 0);
+        assert_eq!(user.lp_token, 100);
+
+        let updated = update_atomically(&mut partner, &mut user, |partner, user| {
+            partner.update_liquidity(100, 300, PRICE_PRECISION as u64)?;
+            user.update_lifetime_stats(0, 0, 300, PRICE_PRECISION as u64, 1)
+        });
+        assert_eq!(updated, Some(()));
+        assert_eq!(partner.total_lp, 300);
+        assert_eq!(partner.total_deposited(), 200);
+        assert_eq!(user.total_deposited, 200);
+    }
+
+    #[test]
+    fn test_legacy_layout() -> Result<()> {
+        // borsh layouts of deployed accounts, including discriminator
+        let mut data = Vec::new();
+        Partner::default().try_serialize(&mut data)?;
+        assert_eq!(data.len(), 104);
+        let mut data = Vec::new();
+        User::default().try_serialize(&mut data)?;
+        assert_eq!(data.len(), 89);
+        Ok(())
+    }
+}
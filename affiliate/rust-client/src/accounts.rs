@@ -0,0 +1,207 @@
+//! Account fetchers
+
+use crate::amount::get_expected_amount;
+use crate::instructions;
+use crate::pda;
+use crate::types::{UserPosition, VaultInfo};
+use affiliate::vault_utils::VirtualPrice;
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anyhow::{anyhow, Result};
+use bincode::deserialize;
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar;
+use solana_program::sysvar::clock::Clock;
+use solana_sdk::signer::Signer;
+use std::convert::TryFrom;
+use std::ops::Deref;
+
+/// current unix timestamp of the node
+pub async fn fetch_clock_time<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+) -> Result<u64> {
+    let rpc = program_client.rpc();
+    let clock_account = rpc.get_account(&sysvar::clock::id())?;
+    let clock = deserialize::<Clock>(&clock_account.data)?;
+    Ok(u64::try_from(clock.unix_timestamp)?)
+}
+
+/// vault with its token accounts and mint decimals
+pub async fn fetch_vault<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+) -> Result<VaultInfo> {
+    let state: mercurial_vault::state::Vault = program_client.account(vault).await?;
+    let token_mint: anchor_spl::token::Mint = program_client.account(state.token_mint).await?;
+    let lp_mint: anchor_spl::token::Mint = program_client.account(state.lp_mint).await?;
+    Ok(VaultInfo {
+        vault,
+        token_vault: state.token_vault,
+        token_mint: state.token_mint,
+        lp_mint: state.lp_mint,
+        token_decimals: token_mint.decimals,
+        lp_decimals: lp_mint.decimals,
+        state,
+    })
+}
+
+/// virtual price of vault as computed by the program at current node time
+pub async fn fetch_virtual_price<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: &VaultInfo,
+) -> Result<u64> {
+    let lp_mint: anchor_spl::token::Mint = program_client.account(vault.lp_mint).await?;
+    let current_time = fetch_clock_time(program_client).await?;
+    vault
+        .state
+        .get_virtual_price(current_time, lp_mint.supply)
+        .ok_or_else(|| anyhow!("cannot compute virtual price, lp supply is zero"))
+}
+
+/// partner state, partners still in borsh layout are converted to zero-copy layout
+pub async fn fetch_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+) -> Result<affiliate::PartnerV2> {
+    let data = program_client.rpc().get_account_data(&partner)?;
+    decode_partner(&data)
+}
+
+/// decode partner in zero-copy or borsh layout
+pub fn decode_partner(data: &[u8]) -> Result<affiliate::PartnerV2> {
+    if data.starts_with(&affiliate::PartnerV2::DISCRIMINATOR) {
+        return Ok(affiliate::PartnerV2::try_deserialize(&mut &data[..])?);
+    }
+    let partner = affiliate::Partner::try_deserialize(&mut &data[..])?;
+    Ok(affiliate::PartnerV2::from(&partner))
+}
+
+/// whether partner account is still in borsh layout and must be migrated
+pub async fn fetch_partner_needs_migration<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+) -> Result<bool> {
+    let data = program_client.rpc().get_account_data(&partner)?;
+    Ok(!data.starts_with(&affiliate::PartnerV2::DISCRIMINATOR))
+}
+
+/// user state, none if user is not created, users still in borsh layout are converted to
+/// zero-copy layout
+pub async fn fetch_user<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    user: Pubkey,
+) -> Result<Option<affiliate::UserV2>> {
+    let rpc = program_client.rpc();
+    match rpc
+        .get_account_with_commitment(&user, rpc.commitment())?
+        .value
+    {
+        Some(account) => Ok(Some(decode_user(&account.data)?)),
+        None => Ok(None),
+    }
+}
+
+/// decode user in zero-copy or borsh layout
+pub fn decode_user(data: &[u8]) -> Result<affiliate::UserV2> {
+    if data.starts_with(&affiliate::UserV2::DISCRIMINATOR) {
+        return Ok(affiliate::UserV2::try_deserialize(&mut &data[..])?);
+    }
+    let user = affiliate::User::try_deserialize(&mut &data[..])?;
+    Ok(affiliate::UserV2::from(&user))
+}
+
+/// whether user account is created and still in borsh layout, so it must be migrated
+pub async fn fetch_user_needs_migration<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    user: Pubkey,
+) -> Result<bool> {
+    let rpc = program_client.rpc();
+    Ok(rpc
+        .get_account_with_commitment(&user, rpc.commitment())?
+        .value
+        .map_or(false, |account| {
+            !account.data.starts_with(&affiliate::UserV2::DISCRIMINATOR)
+        }))
+}
+
+/// migrate instructions of partner and user still in borsh layout, to be prepended to instructions
+/// loading them, user is skipped when it is none or not created
+pub async fn fetch_migrate_instructions<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+    user: Option<Pubkey>,
+) -> Result<Vec<Instruction>> {
+    let mut migrate_instructions = vec![];
+    if fetch_partner_needs_migration(program_client, partner).await? {
+        migrate_instructions.push(instructions::migrate_partner(
+            partner,
+            program_client.payer(),
+        ));
+    }
+    if let Some(user) = user {
+        if fetch_user_needs_migration(program_client, user).await? {
+            migrate_instructions.push(instructions::migrate_user(user));
+        }
+    }
+    Ok(migrate_instructions)
+}
+
+/// users of partner in either layout, with whether each user is still in borsh layout
+pub async fn fetch_partner_users<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+) -> Result<Vec<(Pubkey, affiliate::UserV2, bool)>> {
+    let users = program_client
+        .accounts::<affiliate::UserV2>(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            affiliate::UserV2::PARTNER_OFFSET,
+            partner.as_ref(),
+        ))])
+        .await?;
+    let legacy_users = program_client
+        .accounts::<affiliate::User>(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            affiliate::User::PARTNER_OFFSET,
+            partner.as_ref(),
+        ))])
+        .await?;
+    Ok(users
+        .into_iter()
+        .map(|(user, state)| (user, state, false))
+        .chain(
+            legacy_users
+                .iter()
+                .map(|(user, state)| (*user, affiliate::UserV2::from(state), true)),
+        )
+        .collect())
+}
+
+/// position of owner in vault through partner wallet, none if user is not created
+pub async fn fetch_user_position<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+) -> Result<Option<UserPosition>> {
+    let partner = pda::derive_partner(
+        vault.vault,
+        pda::derive_partner_token(partner_wallet, vault.token_mint),
+    );
+    let user = pda::derive_user(partner, owner);
+    let state = match fetch_user(program_client, user).await? {
+        Some(state) => state,
+        None => return Ok(None),
+    };
+    let partner_state = fetch_partner(program_client, partner).await?;
+    let virtual_price = fetch_virtual_price(program_client, vault).await?;
+    let amount = get_expected_amount(state.lp_token(), virtual_price)
+        .ok_or_else(|| anyhow!("position amount overflows"))?;
+    Ok(Some(UserPosition {
+        user,
+        partner,
+        lp_token: state.lp_token(),
+        virtual_price,
+        amount,
+        fee_ratio: state.get_fee_ratio(partner_state.fee_ratio),
+        state,
+    }))
+}
@@ -1,39 +1,40 @@
-use crate::utils::{default_keypair, get_or_create_ata, simulate_transaction};
-use anyhow::Result;
+use crate::utils::{
+    self, default_keypair, get_authority, get_or_create_ata, simulate_transaction, with_role_signer,
+};
+use affiliate_sdk::{accounts, instructions, pda};
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anyhow::{anyhow, Result};
 use hyper::Client;
 use hyper_tls::HttpsConnector;
 use serde::Deserialize;
-use solana_program::sysvar;
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
-use solana_sdk::system_program;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::str::FromStr;
+
 pub async fn init_partner<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
     partner: String,
+    role_signer: Option<&Keypair>,
 ) -> Result<()> {
-    let partner = Pubkey::from_str(&partner).unwrap();
-    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
-    let token_mint = vault_state.token_mint;
-    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    get_or_create_ata(program_client, vault.token_mint, partner_wallet).await?;
 
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::InitPartner {
-            partner,
-            vault,
-            partner_token,
-            admin: program_client.payer(),
-            system_program: system_program::id(),
-            rent: sysvar::rent::ID,
-            token_program: spl_token::id(),
-        })
-        .args(affiliate::instruction::InitPartner {});
+        .instruction(instructions::init_partner(
+            &vault,
+            partner_wallet,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -46,23 +47,61 @@ pub async fn update_fee_ratio<C: Deref<Target = impl Signer> + Clone>(
     vault: Pubkey,
     partner: String,
     fee_ratio: u64,
+    role_signer: Option<&Keypair>,
 ) -> Result<()> {
-    let partner = Pubkey::from_str(&partner).unwrap();
-    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
-    let token_mint = vault_state.token_mint;
-    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-    // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
 
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::UpdateFeeRatio {
-            partner,
-            admin: program_client.payer(),
+        .instruction(instructions::update_fee_ratio(
+            &vault,
+            partner_wallet,
+            fee_ratio,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn update_user_fee_ratio<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    owner: String,
+    fee_ratio: Option<u64>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let owner = Pubkey::from_str(&owner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    let user = pda::derive_user(partner, owner);
+    // check whether user is existed
+    accounts::fetch_user(program_client, user)
+        .await?
+        .ok_or_else(|| anyhow!("user is not created"))?;
+
+    let builder = accounts::fetch_migrate_instructions(program_client, partner, Some(user))
+        .await?
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
         })
-        .args(affiliate::instruction::UpdateFeeRatio { fee_ratio });
+        .instruction(instructions::update_user_fee_ratio(
+            &vault,
+            partner_wallet,
+            owner,
+            fee_ratio,
+            program_client.payer(),
+        ));
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -70,33 +109,370 @@ pub async fn update_fee_ratio<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
-pub async fn fund_partner<C: Deref<Target = impl Signer> + Clone>(
+pub async fn init_config<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+) -> Result<()> {
+    let builder = program_client
+        .request()
+        .instruction(instructions::init_config(program_client.payer()));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn set_funders<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    treasury: String,
+    funders: Vec<String>,
+) -> Result<()> {
+    let treasury = Pubkey::from_str(&treasury).unwrap();
+    let funders = funders
+        .iter()
+        .map(|funder| Pubkey::from_str(funder).unwrap())
+        .collect();
+    let builder = program_client
+        .request()
+        .instruction(instructions::set_funders(
+            program_client.payer(),
+            treasury,
+            funders,
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn set_roles<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner_manager: String,
+    fee_manager: String,
+    pauser: String,
+) -> Result<()> {
+    let builder = program_client
+        .request()
+        .instruction(instructions::set_roles(
+            program_client.payer(),
+            Pubkey::from_str(&partner_manager).unwrap(),
+            Pubkey::from_str(&fee_manager).unwrap(),
+            Pubkey::from_str(&pauser).unwrap(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn pause_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    paused: bool,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::pause_partner(
+            &vault,
+            partner_wallet,
+            paused,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn close_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = accounts::fetch_partner(program_client, partner).await?;
+    let partner_group = if partner_state.group == Pubkey::default() {
+        None
+    } else {
+        Some(partner_state.group)
+    };
+    // partner token receives balance of rebate escrow
+    get_or_create_ata(program_client, vault.token_mint, partner_wallet).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::close_partner(
+            &vault,
+            partner_wallet,
+            partner_group,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// count every user created before partner counters, then mark partner as backfilled,
+// so close_partner can rely on user_count and total_lp
+pub async fn backfill_partner_users<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // count_user loads partner and user in zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    utils::migrate_partner_users(program_client, partner).await?;
+
+    let users = accounts::fetch_partner_users(program_client, partner).await?;
+    let uncounted_users: Vec<Pubkey> = users
+        .iter()
+        .filter(|(_, user_state, _)| !user_state.counted())
+        .map(|(user, _, _)| *user)
+        .collect();
+    println!(
+        "partner {} has {} users, {} not counted",
+        partner,
+        users.len(),
+        uncounted_users.len()
+    );
+    for chunk in uncounted_users.chunks(COUNT_USER_BATCH_SIZE) {
+        let mut builder = program_client.request();
+        for user in chunk.iter() {
+            builder = builder.instruction(instructions::count_user(partner, *user));
+        }
+        let signature = builder.send().await?;
+        println!("count {} users {}", chunk.len(), signature);
+    }
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::set_users_backfilled(
+            &vault,
+            partner_wallet,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// count_user instructions packed in one transaction
+const COUNT_USER_BATCH_SIZE: usize = 10;
+
+// point referral code to another partner wallet, wallet must be a partner of the vault, must be
+// called by admin, partner manager or partner wallet of the code
+pub async fn reassign_referral_code<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    code: String,
+    partner: String,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::reassign_referral_code(
+            &vault,
+            &code,
+            partner_wallet,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// must be called by admin, partner manager or partner wallet of the code, rent is returned to payer
+// of the code
+pub async fn close_referral_code<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    code: String,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let referral_code: affiliate::ReferralCode = program_client
+        .account(pda::derive_referral_code(&code))
+        .await?;
+    let builder = program_client
+        .request()
+        .instruction(instructions::close_referral_code(
+            &code,
+            referral_code.payer,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn view_config<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+) -> Result<()> {
+    let config_state: affiliate::Config = program_client.account(pda::derive_config()).await?;
+    println!("{:?}", config_state);
+    Ok(())
+}
+
+// invoice or reference is stored on-chain as its sha256 hash
+fn get_reference_hash(reference: Option<String>) -> Option<[u8; 32]> {
+    reference.map(|reference| hash(reference.as_bytes()).to_bytes())
+}
+
+pub async fn payout_statement<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
     partner: String,
-    amount: u64,
 ) -> Result<()> {
     let partner = Pubkey::from_str(&partner).unwrap();
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
     let token_mint = vault_state.token_mint;
     let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
+    let partner = pda::derive_partner(vault, partner_token);
     // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
+    let partner_state = accounts::fetch_partner(program_client, partner).await?;
 
-    let funder_token =
-        get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    println!(
+        "partner {} payouts {} outstanding_fee {} cumulative_fee {}",
+        partner,
+        partner_state.payout_count,
+        partner_state.outstanding_fee,
+        partner_state.cumulative_fee()
+    );
+    let mut total_amount = 0u128;
+    for index in 0..partner_state.payout_count {
+        let payout_receipt = pda::derive_payout_receipt(partner, index);
+        let receipt: affiliate::PayoutReceipt = program_client.account(payout_receipt).await?;
+        println!(
+            "#{} {:?} amount {} timestamp {} funder {} outstanding_fee {} -> {} reference {}",
+            receipt.index,
+            receipt.payout_type,
+            receipt.amount,
+            receipt.timestamp,
+            receipt.funder,
+            receipt.outstanding_fee_before,
+            receipt.outstanding_fee_after,
+            receipt
+                .reference
+                .map(|reference| Hash::new_from_array(reference).to_string())
+                .unwrap_or_default()
+        );
+        total_amount += u128::from(receipt.amount);
+    }
+    println!("total paid {}", total_amount);
+
+    Ok(())
+}
+
+pub async fn fund_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    amount: u64,
+    reference: Option<String>,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = accounts::fetch_partner(program_client, partner).await?;
+
+    let funder = get_authority(program_client, role_signer);
+    get_or_create_ata(program_client, vault.token_mint, funder).await?;
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::FundPartner {
-            partner,
-            partner_token,
-            funder_token,
-            funder: program_client.payer(),
-            token_program: spl_token::id(),
-        })
-        .args(affiliate::instruction::FundPartner { amount });
+        .instruction(instructions::fund_partner(
+            &vault,
+            partner_wallet,
+            &partner_state,
+            amount,
+            get_reference_hash(reference),
+            funder,
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn fund_partner_lp<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    lp_amount: u64,
+    reference: Option<String>,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = accounts::fetch_partner(program_client, partner).await?;
+    if partner_state.partner_lp == Pubkey::default() {
+        println!("partner {} has not set partner lp", partner);
+        return Ok(());
+    }
+
+    let funder = get_authority(program_client, role_signer);
+    get_or_create_ata(program_client, vault.lp_mint, funder).await?;
+    let builder = program_client
+        .request()
+        .instruction(instructions::fund_partner_lp(
+            &vault,
+            partner_wallet,
+            &partner_state,
+            lp_amount,
+            get_reference_hash(reference),
+            funder,
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -104,6 +480,61 @@ pub async fn fund_partner<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
+pub async fn fund_partner_vesting<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    amount: u64,
+    cliff_duration: u64,
+    duration: u64,
+    reference: Option<String>,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = accounts::fetch_partner(program_client, partner).await?;
+
+    let funder = get_authority(program_client, role_signer);
+    get_or_create_ata(program_client, vault.token_mint, funder).await?;
+    let builder = program_client
+        .request()
+        .instruction(instructions::fund_partner_vesting(
+            &vault,
+            partner_wallet,
+            &partner_state,
+            amount,
+            cliff_duration,
+            duration,
+            get_reference_hash(reference),
+            funder,
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// migrate partner and every user of partner still in borsh layout to zero-copy layout
+pub async fn migrate_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner = Pubkey::from_str(&partner).unwrap();
+    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
+    let token_mint = vault_state.token_mint;
+    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
+    let partner = pda::derive_partner(vault, partner_token);
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    utils::migrate_partner_users(program_client, partner).await
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VaultList(Vec<VaultInfo>);
 
@@ -117,6 +548,7 @@ pub struct VaultInfo {
 pub async fn init_partner_all_vault<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     partner: String,
+    role_signer: Option<&Keypair>,
 ) -> Result<()> {
     let partner = Pubkey::from_str(&partner).unwrap();
     let url = "https://merv2-api.mercurial.finance/vault_info";
@@ -130,22 +562,14 @@ pub async fn init_partner_all_vault<C: Deref<Target = impl Signer> + Clone>(
 
     let vault_list: VaultList = serde_json::from_slice(&buf)?;
 
+    let partner_group = get_or_create_partner_group(program_client, partner, role_signer).await?;
+
     for vault in vault_list.0.iter() {
         let token_mint = Pubkey::from_str(&vault.token_address).unwrap();
-        let (vault_pubkey, _) = Pubkey::find_program_address(
-            &[
-                b"vault".as_ref(),
-                token_mint.as_ref(),
-                mercurial_vault::get_base_key().as_ref(),
-            ],
-            &mercurial_vault::id(),
-        );
+        let vault_pubkey = pda::derive_vault(token_mint, mercurial_vault::get_base_key());
 
         let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-        let (partner_pubkey, _nonce) = Pubkey::find_program_address(
-            &[vault_pubkey.as_ref(), partner_token.as_ref()],
-            &affiliate::id(),
-        );
+        let partner_pubkey = pda::derive_partner(vault_pubkey, partner_token);
         // check whether partner is existed
         if program_client
             .rpc()
@@ -157,14 +581,160 @@ pub async fn init_partner_all_vault<C: Deref<Target = impl Signer> + Clone>(
                 partner.to_string(),
                 vault.symbol
             );
-            init_partner(program_client, vault_pubkey, partner.to_string()).await?;
+            init_partner(
+                program_client,
+                vault_pubkey,
+                partner.to_string(),
+                role_signer,
+            )
+            .await?;
         } else {
             println!(
                 "partner {} with vault {} is existed",
                 partner.to_string(),
                 vault.symbol
             );
+            utils::migrate_partner_if_needed(program_client, partner_pubkey).await?;
         }
+
+        let partner_state = accounts::fetch_partner(program_client, partner_pubkey).await?;
+        if partner_state.group != partner_group {
+            println!("link partner {} to group {}", partner_pubkey, partner_group);
+            link_partner_group(program_client, vault_pubkey, partner, role_signer).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_or_create_partner_group<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner_wallet: Pubkey,
+    role_signer: Option<&Keypair>,
+) -> Result<Pubkey> {
+    let partner_group = pda::derive_partner_group(partner_wallet);
+    if program_client
+        .rpc()
+        .get_account_data(&partner_group)
+        .is_err()
+    {
+        println!("init partner group {}", partner_group);
+        let builder = program_client
+            .request()
+            .instruction(instructions::init_partner_group(
+                partner_wallet,
+                get_authority(program_client, role_signer),
+                role_signer.is_some(),
+            ));
+        let builder = with_role_signer(builder, role_signer);
+
+        let signature = builder.send().await?;
+        println!("{}", signature);
+    }
+    Ok(partner_group)
+}
+
+pub async fn link_partner_group<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner_wallet: Pubkey,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let vault = accounts::fetch_vault(program_client, vault).await?;
+    let builder = program_client
+        .request()
+        .instruction(instructions::link_partner_group(
+            &vault,
+            partner_wallet,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// partners linked to group, only partners in zero-copy layout can be linked
+pub async fn get_group_partners<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner_group: Pubkey,
+) -> Result<Vec<(Pubkey, affiliate::PartnerV2)>> {
+    let partners = program_client
+        .accounts::<affiliate::PartnerV2>(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            affiliate::PartnerV2::GROUP_OFFSET,
+            partner_group.as_ref(),
+        ))])
+        .await?;
+    Ok(partners)
+}
+
+pub async fn update_group_fee_ratio<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: String,
+    fee_ratio: u64,
+    role_signer: Option<&Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let partner_group = pda::derive_partner_group(partner_wallet);
+    let partners: Vec<Pubkey> = get_group_partners(program_client, partner_group)
+        .await?
+        .iter()
+        .map(|(partner, _)| *partner)
+        .collect();
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::update_group_fee_ratio(
+            partner_wallet,
+            &partners,
+            fee_ratio,
+            get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn view_partner_group<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let partner_group = pda::derive_partner_group(partner_wallet);
+    let group_state: affiliate::PartnerGroup = program_client.account(partner_group).await?;
+    println!("{:?}", group_state);
+
+    // totals per token mint
+    let mut totals: BTreeMap<Pubkey, (u64, u128, u64)> = BTreeMap::new();
+    for (partner, partner_state) in get_group_partners(program_client, partner_group).await? {
+        let vault_state: mercurial_vault::state::Vault =
+            program_client.account(partner_state.vault).await?;
+        println!(
+            "partner {} vault {} mint {} fee_ratio {} outstanding_fee {} cumulative_fee {}",
+            partner,
+            partner_state.vault,
+            vault_state.token_mint,
+            partner_state.fee_ratio,
+            partner_state.outstanding_fee,
+            partner_state.cumulative_fee()
+        );
+        let total = totals.entry(vault_state.token_mint).or_default();
+        total.0 += partner_state.outstanding_fee;
+        total.1 += partner_state.cumulative_fee();
+        total.2 += partner_state.user_count;
+    }
+    for (token_mint, (outstanding_fee, cumulative_fee, user_count)) in totals.iter() {
+        println!(
+            "mint {} outstanding_fee {} cumulative_fee {} user_count {}",
+            token_mint, outstanding_fee, cumulative_fee, user_count
+        );
     }
 
     Ok(())
@@ -0,0 +1,143 @@
+//! Ui amount conversion and expected amounts at virtual price
+
+use crate::types::Slippage;
+use affiliate::vault_utils::PRICE_PRECISION;
+use anyhow::{anyhow, Result};
+use std::convert::TryFrom;
+
+/// parse amount given as ui amount in mint decimals (e.g. 100.5), or max when max amount is known
+pub fn parse_amount(amount: &str, decimals: u8, max_amount: Option<u64>) -> Result<u64> {
+    if amount.eq_ignore_ascii_case("max") {
+        return max_amount.ok_or_else(|| anyhow!("max is not supported for this amount"));
+    }
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+        return Err(anyhow!("invalid amount {}", amount));
+    }
+    if fraction.len() > usize::from(decimals) {
+        return Err(anyhow!(
+            "amount {} has more than {} decimals",
+            amount,
+            decimals
+        ));
+    }
+    let raw_amount = format!(
+        "{}{:0<width$}",
+        whole,
+        fraction,
+        width = usize::from(decimals)
+    );
+    raw_amount
+        .parse::<u64>()
+        .map_err(|_| anyhow!("amount {} is too large", amount))
+}
+
+/// format raw amount as ui amount in mint decimals
+pub fn to_ui_amount(amount: impl Into<u128>, decimals: u8) -> String {
+    let decimals = usize::from(decimals);
+    let amount = format!("{:0>width$}", amount.into(), width = decimals + 1);
+    let (whole, fraction) = amount.split_at(amount.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// format raw amount together with ui amount, e.g. 100500000 (100.5)
+pub fn format_amount(amount: impl Into<u128>, decimals: u8) -> String {
+    let amount = amount.into();
+    format!("{} ({})", amount, to_ui_amount(amount, decimals))
+}
+
+/// lp expected to be minted for token amount at virtual price
+pub fn get_expected_lp(token_amount: u64, virtual_price: u64) -> Option<u128> {
+    u128::from(token_amount)
+        .checked_mul(PRICE_PRECISION)?
+        .checked_div(u128::from(virtual_price))
+}
+
+/// token amount expected to be withdrawn for lp at virtual price
+pub fn get_expected_amount(lp_amount: u64, virtual_price: u64) -> Option<u128> {
+    affiliate::lp_to_amount(lp_amount, virtual_price)
+}
+
+impl Slippage {
+    /// reduce expected amount by slippage, unless min_out is set
+    pub fn get_min_out(&self, expected_amount: u128) -> Result<u64> {
+        if let Some(min_out) = self.min_out {
+            return Ok(min_out);
+        }
+        let bps = u128::from(self.slippage_bps.min(10_000));
+        let min_out = expected_amount * (10_000 - bps) / 10_000;
+        Ok(u64::try_from(min_out)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() -> Result<()> {
+        assert_eq!(parse_amount("100.5", 6, None)?, 100_500_000);
+        assert_eq!(parse_amount("100", 6, None)?, 100_000_000);
+        assert_eq!(parse_amount(".5", 6, None)?, 500_000);
+        assert_eq!(parse_amount("5.", 6, None)?, 5_000_000);
+        assert_eq!(parse_amount("42", 0, None)?, 42);
+        assert_eq!(parse_amount("MAX", 6, Some(7))?, 7);
+        assert!(parse_amount("max", 6, None).is_err());
+        assert!(parse_amount("0.0000001", 6, None).is_err());
+        assert!(parse_amount(".", 6, None).is_err());
+        assert!(parse_amount("-1", 6, None).is_err());
+        assert!(parse_amount("1e6", 6, None).is_err());
+        assert!(parse_amount("18446744073709.551616", 6, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ui_amount_round_trip() -> Result<()> {
+        for (amount, decimals, ui_amount) in [
+            (100_500_000u64, 6, "100.5"),
+            (100_000_000, 6, "100"),
+            (1, 9, "0.000000001"),
+            (0, 9, "0"),
+            (42, 0, "42"),
+            (u64::MAX, 9, "18446744073.709551615"),
+        ] {
+            assert_eq!(to_ui_amount(amount, decimals), ui_amount);
+            assert_eq!(parse_amount(ui_amount, decimals, None)?, amount);
+        }
+        assert_eq!(format_amount(100_500_000u64, 6), "100500000 (100.5)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_min_out() -> Result<()> {
+        let slippage = Slippage {
+            slippage_bps: 50,
+            min_out: None,
+        };
+        assert_eq!(slippage.get_min_out(10_000)?, 9_950);
+        assert_eq!(slippage.get_min_out(199)?, 198);
+        assert_eq!(slippage.get_min_out(0)?, 0);
+        assert!(slippage.get_min_out(u128::from(u64::MAX) * 2).is_err());
+
+        // slippage is capped at 100%
+        let slippage = Slippage {
+            slippage_bps: 20_000,
+            min_out: None,
+        };
+        assert_eq!(slippage.get_min_out(10_000)?, 0);
+
+        // explicit min_out ignores expected amount
+        let slippage = Slippage {
+            slippage_bps: 50,
+            min_out: Some(7),
+        };
+        assert_eq!(slippage.get_min_out(10_000)?, 7);
+        Ok(())
+    }
+}
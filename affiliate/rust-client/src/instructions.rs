@@ -0,0 +1,1033 @@
+//! Instruction builders of user, partner and admin flows, accounts are derived from vault and
+//! partner wallet
+
+use crate::pda;
+use crate::types::{BasketLeg, VaultInfo};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+use solana_program::sysvar;
+
+// user token and trailing native SOL accounts, temporary wsol account is used for native SOL
+fn get_user_token(vault: &VaultInfo, user: Pubkey, owner: Pubkey) -> (Pubkey, Option<Pubkey>) {
+    if vault.is_native() {
+        return (pda::derive_temp_wsol(user), Some(vault.token_mint));
+    }
+    (
+        spl_associated_token_account::get_associated_token_address(&owner, &vault.token_mint),
+        None,
+    )
+}
+
+fn get_partner(vault: &VaultInfo, partner_wallet: Pubkey) -> Pubkey {
+    pda::derive_partner(
+        vault.vault,
+        pda::derive_partner_token(partner_wallet, vault.token_mint),
+    )
+}
+
+/// resize partner account created with previous space, payer funds the additional rent
+pub fn resize_partner(partner: Pubkey, payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ResizePartner {
+            partner,
+            payer,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::ResizePartner {}.data(),
+    }
+}
+
+/// migrate partner from borsh layout to zero-copy layout, payer funds the additional rent
+pub fn migrate_partner(partner: Pubkey, payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ResizePartner {
+            partner,
+            payer,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::MigratePartner {}.data(),
+    }
+}
+
+/// migrate user from borsh layout to zero-copy layout, can be sent by anyone
+pub fn migrate_user(user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::MigrateUser { user }.to_account_metas(None),
+        data: affiliate::instruction::MigrateUser {}.data(),
+    }
+}
+
+/// count user created before partner counters in user_count and total_lp, can be sent by anyone
+pub fn count_user(partner: Pubkey, user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::CountUser { partner, user }.to_account_metas(None),
+        data: affiliate::instruction::CountUser {}.data(),
+    }
+}
+
+/// create user PDA of owner under partner, payer funds the account
+pub fn init_user_permissionless(partner: Pubkey, owner: Pubkey, payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitUserPermissionless {
+            user: pda::derive_user(partner, owner),
+            partner,
+            owner,
+            payer,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitUserPermissionless {}.data(),
+    }
+}
+
+/// deposit token of owner through partner wallet, user lp token account must exist
+pub fn deposit(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+    token_amount: u64,
+    minimum_lp_token_amount: u64,
+    campaign_id: Option<u32>,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    let user = pda::derive_user(partner, owner);
+    let (user_token, native_mint) = get_user_token(vault, user, owner);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::DepositWithdrawLiquidity {
+            partner,
+            user,
+            vault_program: mercurial_vault::id(),
+            vault: vault.vault,
+            token_vault: vault.token_vault,
+            vault_lp_mint: vault.lp_mint,
+            user_token,
+            user_lp: pda::derive_user_lp(user, vault.lp_mint),
+            owner,
+            token_program: spl_token::id(),
+            campaign: campaign_id.map(|campaign_id| pda::derive_campaign(partner, campaign_id)),
+            native_mint,
+            // always passed so partner in borsh layout can be migrated in place
+            system_program: Some(system_program::id()),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::Deposit {
+            token_amount,
+            minimum_lp_token_amount,
+            campaign_id,
+        }
+        .data(),
+    }
+}
+
+/// deposit token of depositor on behalf of beneficiary, user PDA of beneficiary must exist, beneficiary
+/// only derives the user PDA, deposit has no campaign and no native SOL path
+pub fn deposit_for(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    beneficiary: Pubkey,
+    depositor: Pubkey,
+    token_amount: u64,
+    minimum_lp_token_amount: u64,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    let user = pda::derive_user(partner, beneficiary);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::DepositFor {
+            partner,
+            user,
+            vault_program: mercurial_vault::id(),
+            vault: vault.vault,
+            token_vault: vault.token_vault,
+            vault_lp_mint: vault.lp_mint,
+            depositor_token: spl_associated_token_account::get_associated_token_address(
+                &depositor,
+                &vault.token_mint,
+            ),
+            user_lp: pda::derive_user_lp(user, vault.lp_mint),
+            depositor,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::DepositFor {
+            token_amount,
+            minimum_lp_token_amount,
+        }
+        .data(),
+    }
+}
+
+/// deposit token of owner to several vaults in one instruction, user PDA and user lp token account of
+/// every leg must exist, native SOL is not wrapped
+pub fn deposit_basket(legs: &[BasketLeg<'_>], owner: Pubkey) -> Instruction {
+    let mut accounts = affiliate::accounts::DepositBasket {
+        vault_program: mercurial_vault::id(),
+        owner,
+        token_program: spl_token::id(),
+    }
+    .to_account_metas(None);
+    for leg in legs {
+        let partner = get_partner(leg.vault, leg.partner_wallet);
+        let user = pda::derive_user(partner, owner);
+        // accounts of the leg in the order the program reads them
+        accounts.extend([
+            AccountMeta::new(partner, false),
+            AccountMeta::new(user, false),
+            AccountMeta::new(leg.vault.vault, false),
+            AccountMeta::new(leg.vault.token_vault, false),
+            AccountMeta::new(leg.vault.lp_mint, false),
+            AccountMeta::new(
+                spl_associated_token_account::get_associated_token_address(
+                    &owner,
+                    &leg.vault.token_mint,
+                ),
+                false,
+            ),
+            AccountMeta::new(pda::derive_user_lp(user, leg.vault.lp_mint), false),
+        ]);
+    }
+    Instruction {
+        program_id: affiliate::id(),
+        accounts,
+        data: affiliate::instruction::DepositBasket {
+            token_amounts: legs.iter().map(|leg| leg.token_amount).collect(),
+            minimum_lp_token_amounts: legs.iter().map(|leg| leg.minimum_lp_token_amount).collect(),
+        }
+        .data(),
+    }
+}
+
+/// withdraw lp of owner, fee is attributed to last-touch campaign of user when it is passed
+pub fn withdraw(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+    unmint_amount: u64,
+    min_out_amount: u64,
+    last_campaign_id: Option<u32>,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    let user = pda::derive_user(partner, owner);
+    let (user_token, native_mint) = get_user_token(vault, user, owner);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::DepositWithdrawLiquidity {
+            partner,
+            user,
+            vault_program: mercurial_vault::id(),
+            vault: vault.vault,
+            token_vault: vault.token_vault,
+            vault_lp_mint: vault.lp_mint,
+            user_token,
+            user_lp: pda::derive_user_lp(user, vault.lp_mint),
+            owner,
+            token_program: spl_token::id(),
+            campaign: last_campaign_id
+                .map(|campaign_id| pda::derive_campaign(partner, campaign_id)),
+            native_mint,
+            // always passed so partner in borsh layout can be migrated in place
+            system_program: Some(system_program::id()),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::Withdraw {
+            unmint_amount,
+            min_out_amount,
+            unwrap_sol: native_mint.is_some(),
+        }
+        .data(),
+    }
+}
+
+/// claim rebate of owner from rebate escrow of partner, owner token account must exist
+pub fn claim_rebate(vault: &VaultInfo, partner_wallet: Pubkey, owner: Pubkey) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ClaimRebate {
+            partner,
+            partner_token,
+            vault: vault.vault,
+            user: pda::derive_user(partner, owner),
+            rebate_escrow: pda::derive_rebate_escrow(partner),
+            owner_token: spl_associated_token_account::get_associated_token_address(
+                &owner,
+                &vault.token_mint,
+            ),
+            owner,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::ClaimRebate {}.data(),
+    }
+}
+
+/// accrue points of user, permissionless
+pub fn refresh_points(partner: Pubkey, user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::RefreshPoints { partner, user }.to_account_metas(None),
+        data: affiliate::instruction::RefreshPoints {}.data(),
+    }
+}
+
+/// close user PDA of owner, user must not hold lp
+pub fn close_user(vault: &VaultInfo, partner_wallet: Pubkey, owner: Pubkey) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    let user = pda::derive_user(partner, owner);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::CloseUser {
+            partner,
+            user,
+            user_lp: pda::derive_user_lp(user, vault.lp_mint),
+            owner,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::CloseUser {}.data(),
+    }
+}
+
+/// simulate to get pending fee of user as UserPendingFee event
+pub fn get_user_pending_fee(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::GetUserPendingFee {
+            partner,
+            user: pda::derive_user(partner, owner),
+            vault: vault.vault,
+            vault_lp_mint: vault.lp_mint,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::GetUserPendingFee {}.data(),
+    }
+}
+
+// config is only passed when instruction is signed by a role instead of super admin
+fn get_role_config(role_signed: bool) -> Option<Pubkey> {
+    if role_signed {
+        Some(pda::derive_config())
+    } else {
+        None
+    }
+}
+
+/// create config, signed by super admin
+pub fn init_config(admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitConfig {
+            config: pda::derive_config(),
+            admin,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitConfig {}.data(),
+    }
+}
+
+/// set treasury and funders allowed to fund partners, signed by super admin
+pub fn set_funders(admin: Pubkey, treasury: Pubkey, funders: Vec<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::UpdateConfig {
+            config: pda::derive_config(),
+            admin,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::SetFunders { treasury, funders }.data(),
+    }
+}
+
+/// set partner manager, fee manager and pauser roles, signed by super admin
+pub fn set_roles(
+    admin: Pubkey,
+    partner_manager: Pubkey,
+    fee_manager: Pubkey,
+    pauser: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::UpdateConfig {
+            config: pda::derive_config(),
+            admin,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::SetRoles {
+            partner_manager,
+            fee_manager,
+            pauser,
+        }
+        .data(),
+    }
+}
+
+/// create partner of partner wallet in vault, signed by admin or partner manager
+pub fn init_partner(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitPartner {
+            partner: pda::derive_partner(vault.vault, partner_token),
+            vault: vault.vault,
+            partner_token,
+            admin,
+            system_program: system_program::id(),
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitPartner {}.data(),
+    }
+}
+
+/// update fee ratio of partner, signed by admin or fee manager
+pub fn update_fee_ratio(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    fee_ratio: u64,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::UpdateFeeRatio {
+            partner: get_partner(vault, partner_wallet),
+            admin,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::UpdateFeeRatio { fee_ratio }.data(),
+    }
+}
+
+/// set or clear fee ratio override of user, signed by admin or partner wallet
+pub fn update_user_fee_ratio(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+    fee_ratio: Option<u64>,
+    authority: Pubkey,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::UpdateUserFeeRatio {
+            user: pda::derive_user(partner, owner),
+            partner,
+            partner_token,
+            authority,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::UpdateUserFeeRatio { fee_ratio }.data(),
+    }
+}
+
+/// pause or unpause deposits through partner, signed by admin or pauser, only admin can unpause
+pub fn pause_partner(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    paused: bool,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::PausePartner {
+            partner: get_partner(vault, partner_wallet),
+            admin,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::PausePartner { paused }.data(),
+    }
+}
+
+/// close partner without users, lp and outstanding fee, group is passed when partner is linked,
+/// balance of rebate escrow is returned to partner token of partner wallet
+pub fn close_partner(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    partner_group: Option<Pubkey>,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ClosePartner {
+            partner,
+            partner_group,
+            admin,
+            config: get_role_config(role_signed),
+            rebate_escrow: pda::derive_rebate_escrow(partner),
+            partner_token: pda::derive_partner_token(partner_wallet, vault.token_mint),
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::ClosePartner {}.data(),
+    }
+}
+
+/// mark every user of partner as counted, signed by admin or partner manager
+pub fn set_users_backfilled(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::SetUsersBackfilled {
+            partner: get_partner(vault, partner_wallet),
+            admin,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::SetUsersBackfilled {}.data(),
+    }
+}
+
+/// fund outstanding fee of partner in token, funder token account must exist
+pub fn fund_partner(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    partner_state: &affiliate::PartnerV2,
+    amount: u64,
+    reference: Option<[u8; 32]>,
+    funder: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::FundPartner {
+            partner,
+            partner_token,
+            funder_token: spl_associated_token_account::get_associated_token_address(
+                &funder,
+                &vault.token_mint,
+            ),
+            funder,
+            token_program: spl_token::id(),
+            payout_receipt: pda::derive_payout_receipt(partner, partner_state.payout_count),
+            system_program: system_program::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::FundPartner { amount, reference }.data(),
+    }
+}
+
+/// fund outstanding fee of partner in vault lp, partner lp must be set
+pub fn fund_partner_lp(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    partner_state: &affiliate::PartnerV2,
+    lp_amount: u64,
+    reference: Option<[u8; 32]>,
+    funder: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner = get_partner(vault, partner_wallet);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::FundPartnerLp {
+            partner,
+            partner_lp: partner_state.partner_lp,
+            vault: vault.vault,
+            vault_lp_mint: vault.lp_mint,
+            funder_lp: spl_associated_token_account::get_associated_token_address(
+                &funder,
+                &vault.lp_mint,
+            ),
+            funder,
+            token_program: spl_token::id(),
+            payout_receipt: pda::derive_payout_receipt(partner, partner_state.payout_count),
+            system_program: system_program::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::FundPartnerLp {
+            lp_amount,
+            reference,
+        }
+        .data(),
+    }
+}
+
+/// fund outstanding fee of partner into a vesting escrow released linearly after cliff
+#[allow(clippy::too_many_arguments)]
+pub fn fund_partner_vesting(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    partner_state: &affiliate::PartnerV2,
+    amount: u64,
+    cliff_duration: u64,
+    duration: u64,
+    reference: Option<[u8; 32]>,
+    funder: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    let partner_vesting = pda::derive_partner_vesting(partner, partner_state.vesting_count);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::FundPartnerVesting {
+            partner,
+            partner_token,
+            token_mint: vault.token_mint,
+            partner_vesting,
+            escrow: pda::derive_vesting_escrow(partner_vesting),
+            payout_receipt: pda::derive_payout_receipt(partner, partner_state.payout_count),
+            funder_token: spl_associated_token_account::get_associated_token_address(
+                &funder,
+                &vault.token_mint,
+            ),
+            funder,
+            system_program: system_program::id(),
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::FundPartnerVesting {
+            amount,
+            cliff_duration,
+            duration,
+            reference,
+        }
+        .data(),
+    }
+}
+
+/// release vested fee of vesting to partner token, can be sent by anyone
+pub fn claim_vested(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    partner_vesting: Pubkey,
+    escrow: Pubkey,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ClaimVested {
+            partner: pda::derive_partner(vault.vault, partner_token),
+            partner_token,
+            partner_vesting,
+            escrow,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::ClaimVested {}.data(),
+    }
+}
+
+/// create partner group of partner wallet, signed by admin or partner manager
+pub fn init_partner_group(partner_wallet: Pubkey, admin: Pubkey, role_signed: bool) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitPartnerGroup {
+            partner_group: pda::derive_partner_group(partner_wallet),
+            partner_wallet,
+            admin,
+            system_program: system_program::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitPartnerGroup {}.data(),
+    }
+}
+
+/// link partner to partner group of partner wallet, signed by admin or partner manager
+pub fn link_partner_group(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::LinkPartnerGroup {
+            partner_group: pda::derive_partner_group(partner_wallet),
+            partner: pda::derive_partner(vault.vault, partner_token),
+            partner_token,
+            admin,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::LinkPartnerGroup {}.data(),
+    }
+}
+
+/// update fee ratio of every partner linked to group, partners are passed as remaining accounts
+pub fn update_group_fee_ratio(
+    partner_wallet: Pubkey,
+    partners: &[Pubkey],
+    fee_ratio: u64,
+    admin: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let mut accounts = affiliate::accounts::UpdateGroupFeeRatio {
+        partner_group: pda::derive_partner_group(partner_wallet),
+        admin,
+        config: get_role_config(role_signed),
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        partners
+            .iter()
+            .map(|partner| AccountMeta::new(*partner, false)),
+    );
+    Instruction {
+        program_id: affiliate::id(),
+        accounts,
+        data: affiliate::instruction::UpdateGroupFeeRatio { fee_ratio }.data(),
+    }
+}
+
+/// register referral code of partner wallet, signed by partner wallet, admin or partner manager, signer
+/// pays the rent
+pub fn register_referral_code(
+    vault: &VaultInfo,
+    code: String,
+    partner_wallet: Pubkey,
+    authority: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::RegisterReferralCode {
+            referral_code: pda::derive_referral_code(&code),
+            partner_wallet,
+            partner: pda::derive_partner(vault.vault, partner_token),
+            partner_token,
+            authority,
+            system_program: system_program::id(),
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::RegisterReferralCode { code }.data(),
+    }
+}
+
+/// point referral code to another partner wallet, signed by admin, partner manager or partner wallet of
+/// the code
+pub fn reassign_referral_code(
+    vault: &VaultInfo,
+    code: &str,
+    partner_wallet: Pubkey,
+    authority: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::ReassignReferralCode {
+            referral_code: pda::derive_referral_code(code),
+            partner_wallet,
+            partner: pda::derive_partner(vault.vault, partner_token),
+            partner_token,
+            authority,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::ReassignReferralCode {}.data(),
+    }
+}
+
+/// close referral code, signed by admin, partner manager or partner wallet of the code, rent is
+/// returned to payer of the code
+pub fn close_referral_code(
+    code: &str,
+    rent_receiver: Pubkey,
+    authority: Pubkey,
+    role_signed: bool,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::CloseReferralCode {
+            referral_code: pda::derive_referral_code(code),
+            rent_receiver,
+            authority,
+            config: get_role_config(role_signed),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::CloseReferralCode {}.data(),
+    }
+}
+
+/// create user PDA of owner, partner authority co-signs when partner gates onboarding
+pub fn init_user(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+    partner_authority: Option<Pubkey>,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitUser {
+            user: pda::derive_user(partner, owner),
+            partner,
+            owner,
+            system_program: system_program::id(),
+            rent: sysvar::rent::ID,
+            partner_token: partner_authority.map(|_| partner_token),
+            partner_authority,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitUser {}.data(),
+    }
+}
+
+/// create user PDA of owner under partner wallet of referral code, signed by owner
+pub fn init_user_with_referral_code(
+    vault: &VaultInfo,
+    code: String,
+    partner_wallet: Pubkey,
+    owner: Pubkey,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitUserWithReferralCode {
+            user: pda::derive_user(partner, owner),
+            partner,
+            partner_token,
+            referral_code: pda::derive_referral_code(&code),
+            owner,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitUserWithReferralCode { _code: code }.data(),
+    }
+}
+
+// accounts of partner settings updated by partner wallet or admin
+fn get_partner_setting(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    authority: Pubkey,
+) -> Vec<AccountMeta> {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    affiliate::accounts::UpdatePartnerSetting {
+        partner: pda::derive_partner(vault.vault, partner_token),
+        partner_token,
+        authority,
+    }
+    .to_account_metas(None)
+}
+
+/// require partner wallet to co-sign init_user, signed by partner wallet or admin
+pub fn update_gated_onboarding(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    require_partner_signature: bool,
+    authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: get_partner_setting(vault, partner_wallet, authority),
+        data: affiliate::instruction::UpdateGatedOnboarding {
+            require_partner_signature,
+        }
+        .data(),
+    }
+}
+
+/// update ratio of fee credited back to users, signed by partner wallet or admin
+pub fn update_rebate_ratio(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    rebate_ratio: u64,
+    authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: get_partner_setting(vault, partner_wallet, authority),
+        data: affiliate::instruction::UpdateRebateRatio { rebate_ratio }.data(),
+    }
+}
+
+/// update multiplier of points accrued by users, signed by partner wallet or admin
+pub fn update_points_multiplier(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    points_multiplier: u64,
+    authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: get_partner_setting(vault, partner_wallet, authority),
+        data: affiliate::instruction::UpdatePointsMultiplier { points_multiplier }.data(),
+    }
+}
+
+/// receive fee in vault lp of partner wallet, signed by partner wallet or admin
+pub fn set_partner_lp(vault: &VaultInfo, partner_wallet: Pubkey, authority: Pubkey) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::SetPartnerLp {
+            partner: pda::derive_partner(vault.vault, partner_token),
+            partner_token,
+            vault: vault.vault,
+            partner_lp: spl_associated_token_account::get_associated_token_address(
+                &partner_wallet,
+                &vault.lp_mint,
+            ),
+            authority,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::SetPartnerLp {}.data(),
+    }
+}
+
+/// create rebate escrow of partner, payer funds the account
+pub fn init_rebate_escrow(vault: &VaultInfo, partner_wallet: Pubkey, payer: Pubkey) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitRebateEscrow {
+            partner,
+            partner_token,
+            token_mint: vault.token_mint,
+            rebate_escrow: pda::derive_rebate_escrow(partner),
+            payer,
+            system_program: system_program::id(),
+            rent: sysvar::rent::ID,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitRebateEscrow {}.data(),
+    }
+}
+
+/// create campaign of partner, signed by partner wallet or admin
+pub fn init_campaign(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    campaign_id: u32,
+    authority: Pubkey,
+) -> Instruction {
+    let partner_token = pda::derive_partner_token(partner_wallet, vault.token_mint);
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitCampaign {
+            campaign: pda::derive_campaign(partner, campaign_id),
+            partner,
+            partner_token,
+            authority,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitCampaign { campaign_id }.data(),
+    }
+}
+
+/// create profile of partner wallet, signed by partner wallet
+pub fn init_partner_profile(
+    partner_wallet: Pubkey,
+    name: String,
+    website_uri: String,
+    logo_uri: String,
+    contact: String,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::InitPartnerProfile {
+            partner_profile: pda::derive_partner_profile(partner_wallet),
+            partner_wallet,
+            system_program: system_program::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::InitPartnerProfile {
+            name,
+            website_uri,
+            logo_uri,
+            contact,
+        }
+        .data(),
+    }
+}
+
+/// update profile of partner wallet, signed by partner wallet
+pub fn update_partner_profile(
+    partner_wallet: Pubkey,
+    name: String,
+    website_uri: String,
+    logo_uri: String,
+    contact: String,
+) -> Instruction {
+    Instruction {
+        program_id: affiliate::id(),
+        accounts: affiliate::accounts::UpdatePartnerProfile {
+            partner_profile: pda::derive_partner_profile(partner_wallet),
+            partner_wallet,
+        }
+        .to_account_metas(None),
+        data: affiliate::instruction::UpdatePartnerProfile {
+            name,
+            website_uri,
+            logo_uri,
+            contact,
+        }
+        .data(),
+    }
+}
+
+/// simulate to get summary of partner and passed users as PartnerSummary event
+pub fn get_partner_summary(
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+    users: &[Pubkey],
+) -> Instruction {
+    let mut accounts = affiliate::accounts::GetPartnerSummary {
+        partner: get_partner(vault, partner_wallet),
+        vault: vault.vault,
+        vault_lp_mint: vault.lp_mint,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        users
+            .iter()
+            .map(|user| AccountMeta::new_readonly(*user, false)),
+    );
+    Instruction {
+        program_id: affiliate::id(),
+        accounts,
+        data: affiliate::instruction::GetPartnerSummary {}.data(),
+    }
+}
@@ -1,5 +1,8 @@
-pub mod admin;
-pub mod partner;
-// pub mod strategy_handler;
-pub mod user;
-pub mod utils;
+//! Affiliate sdk: PDA derivation, instruction builders, account fetchers and ui amount helpers,
+//! shared by the rust-client CLI and backend services
+
+pub mod accounts;
+pub mod amount;
+pub mod instructions;
+pub mod pda;
+pub mod types;
@@ -13,12 +13,17 @@ use anyhow::Result;
 
 use clap::Parser;
 use mercurial_vault::get_base_key;
-use solana_sdk::signature::{read_keypair_file, Keypair};
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
 // use strategy_handler::base::get_strategy_handler;
 
-use crate::utils::default_keypair;
+use crate::utils::{
+    default_keypair, get_mint_decimals, get_token_balance, parse_amount, resolve_partner,
+};
 use admin::*;
+use affiliate_sdk::pda;
+use affiliate_sdk::types::Slippage;
 use partner::*;
+use std::ops::Deref;
 use std::rc::Rc;
 use std::str::FromStr;
 use user::*;
@@ -43,6 +48,10 @@ pub struct ConfigOverride {
 
     #[clap(global = true, long = "provider.base")]
     pub base: Option<String>,
+
+    /// Keypair holding an admin role, signs admin commands instead of wallet
+    #[clap(global = true, long = "role-signer")]
+    pub role_signer: Option<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -65,28 +74,258 @@ pub enum VaultCommand {
 
 #[derive(Debug, Parser)]
 pub enum UserCommand {
-    Deposit { token_amount: u64, partner: String },
-    Withdraw { unmint_amount: u64, partner: String },
+    Deposit {
+        /// Ui amount in token decimals, or max
+        token_amount: String,
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+        /// Campaign that deposit is tagged with
+        #[clap(long)]
+        campaign: Option<u32>,
+        /// Max slippage from amount expected at current virtual price, in bps
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+        /// Minimum lp token amount in ui amount, overrides slippage
+        #[clap(long)]
+        min_out: Option<String>,
+    },
+    DepositFor {
+        /// Ui amount in token decimals, or max
+        token_amount: String,
+        beneficiary: String,
+        partner: String,
+        /// Max slippage from amount expected at current virtual price, in bps
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+        /// Minimum lp token amount in ui amount, overrides slippage
+        #[clap(long)]
+        min_out: Option<String>,
+    },
+    DepositBasket {
+        /// Json list of token mint and weight
+        weights_file: String,
+        /// Ui amount in token decimals, every leg must have the same decimals
+        token_amount: String,
+        partner: String,
+        /// Max slippage of every leg from lp expected at current virtual price, in bps
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+    },
+    Withdraw {
+        /// Ui amount in lp decimals, or max
+        unmint_amount: String,
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+        /// Max slippage from amount expected at current virtual price, in bps
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+        /// Minimum token amount in ui amount, overrides slippage
+        #[clap(long)]
+        min_out: Option<String>,
+    },
     // WithdrawFromStrategy {
     //     unmint_amount: u64,
     //     strategy: Pubkey,
     //     partner: String,
     // },
-    ViewUser { partner: String },
+    ViewUser {
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+    },
+    CloseUser {
+        partner: String,
+    },
+    ViewPendingFee {
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+    },
+    ViewRebate {
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+    },
+    ClaimRebate {
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+    },
+    RefreshPoints {
+        partner: String,
+        /// Wallet of the user, default to payer
+        owner: Option<String>,
+    },
+    /// Simulate deposit and withdraw of payer and print the compute units
+    MeasureComputeUnits {
+        partner: String,
+        /// Ui amount in token decimals
+        token_amount: String,
+        /// Ui amount in lp decimals
+        unmint_amount: String,
+    },
 }
 
 #[derive(Debug, Parser)]
 pub enum AdminCommand {
-    InitPartner { partner: String },
-    InitPartnerAllVault { partner: String },
-    UpdateFeeRatio { partner: String, fee_ratio: u64 },
-    FundPartner { partner: String, amount: u64 },
+    InitPartner {
+        partner: String,
+    },
+    InitPartnerAllVault {
+        partner: String,
+    },
+    UpdateFeeRatio {
+        partner: String,
+        fee_ratio: u64,
+    },
+    UpdateUserFeeRatio {
+        partner: String,
+        owner: String,
+        fee_ratio: Option<u64>,
+    },
+    FundPartner {
+        partner: String,
+        /// Ui amount in token decimals
+        amount: String,
+        /// Invoice or reference, stored as sha256 hash in payout receipt
+        #[clap(long)]
+        reference: Option<String>,
+    },
+    FundPartnerLp {
+        partner: String,
+        /// Ui amount in lp decimals
+        lp_amount: String,
+        /// Invoice or reference, stored as sha256 hash in payout receipt
+        #[clap(long)]
+        reference: Option<String>,
+    },
+    FundPartnerVesting {
+        partner: String,
+        /// Ui amount in token decimals
+        amount: String,
+        cliff_duration: u64,
+        duration: u64,
+        /// Invoice or reference, stored as sha256 hash in payout receipt
+        #[clap(long)]
+        reference: Option<String>,
+    },
+    PayoutStatement {
+        partner: String,
+    },
+    InitConfig {},
+    SetFunders {
+        treasury: String,
+        funders: Vec<String>,
+    },
+    ViewConfig {},
+    MigratePartner {
+        partner: String,
+    },
+    UpdateGroupFeeRatio {
+        partner: String,
+        fee_ratio: u64,
+    },
+    ViewPartnerGroup {
+        partner: String,
+    },
+    SetRoles {
+        partner_manager: String,
+        fee_manager: String,
+        pauser: String,
+    },
+    PausePartner {
+        partner: String,
+        #[clap(parse(try_from_str))]
+        paused: bool,
+    },
+    ClosePartner {
+        partner: String,
+    },
+    BackfillPartnerUsers {
+        partner: String,
+    },
+    ReassignReferralCode {
+        code: String,
+        partner: String,
+    },
+    CloseReferralCode {
+        code: String,
+    },
 }
 
 #[derive(Debug, Parser)]
 pub enum PartnerCommand {
-    InitUser { partner: String },
-    ViewPartner { partner: String },
+    InitUser {
+        partner: Option<String>,
+        /// Referral code, resolved to partner
+        #[clap(long)]
+        code: Option<String>,
+        /// Partner wallet keypair, required when partner gates onboarding
+        #[clap(long)]
+        partner_signer: Option<String>,
+    },
+    UpdateGatedOnboarding {
+        partner: String,
+        #[clap(parse(try_from_str))]
+        require_partner_signature: bool,
+    },
+    ViewPartner {
+        partner: String,
+    },
+    RegisterReferralCode {
+        code: String,
+        partner: String,
+    },
+    SetPartnerLp {
+        partner: String,
+    },
+    VestingStatus {
+        partner: String,
+    },
+    ClaimVested {
+        partner: String,
+    },
+    SetPartnerProfile {
+        name: String,
+        website_uri: String,
+        logo_uri: String,
+        contact: String,
+    },
+    UpdateRebateRatio {
+        partner: String,
+        rebate_ratio: u64,
+    },
+    FundRebateEscrow {
+        partner: String,
+        /// Ui amount in token decimals
+        amount: String,
+    },
+    UpdatePointsMultiplier {
+        partner: String,
+        points_multiplier: u64,
+    },
+    PointsLeaderboard {
+        partner: String,
+    },
+    InitCampaign {
+        partner: String,
+        campaign_id: u32,
+    },
+    ViewCampaign {
+        partner: String,
+        campaign_id: u32,
+    },
+    PartnerSummary {
+        partner: String,
+    },
 }
 
 #[derive(Parser)]
@@ -105,6 +344,11 @@ async fn main() -> Result<()> {
         Some(wallet) => read_keypair_file(wallet).expect("Requires a keypair file"),
         None => default_keypair(),
     };
+    let role_signer = opts
+        .cfg_override
+        .role_signer
+        .map(|path| read_keypair_file(path).expect("Requires a keypair file"));
+    let role_signer = role_signer.as_ref();
     let url = match opts.cfg_override.cluster {
         Some(cluster) => cluster,
         None => Cluster::Devnet,
@@ -133,10 +377,7 @@ async fn main() -> Result<()> {
         None => get_base_key(),
     };
 
-    let (vault, _) = Pubkey::find_program_address(
-        &[b"vault".as_ref(), token_mint.as_ref(), base.as_ref()],
-        &mercurial_vault::id(),
-    );
+    let vault = pda::derive_vault(token_mint, base);
 
     println!("ProgramID {}", program_id.to_string());
     println!("TOKEN MINT {}", token_mint);
@@ -155,11 +396,105 @@ async fn main() -> Result<()> {
             UserCommand::Deposit {
                 token_amount,
                 partner,
-            } => deposit(&program_client, token_mint, base, partner, token_amount).await?,
+                code,
+                campaign,
+                slippage_bps,
+                min_out,
+            } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                let token_decimals = get_mint_decimals(&program_client, token_mint).await?;
+                let max_amount = get_deposit_max_amount(&program_client, token_mint).await?;
+                let token_amount = parse_amount(&token_amount, token_decimals, max_amount)?;
+                let min_out = parse_min_out(&program_client, vault, min_out, true).await?;
+                deposit(
+                    &program_client,
+                    token_mint,
+                    base,
+                    partner,
+                    token_amount,
+                    campaign,
+                    Slippage {
+                        slippage_bps,
+                        min_out,
+                    },
+                )
+                .await?
+            }
+            UserCommand::DepositFor {
+                token_amount,
+                beneficiary,
+                partner,
+                slippage_bps,
+                min_out,
+            } => {
+                let token_decimals = get_mint_decimals(&program_client, token_mint).await?;
+                let max_amount = get_deposit_max_amount(&program_client, token_mint).await?;
+                let token_amount = parse_amount(&token_amount, token_decimals, max_amount)?;
+                let min_out = parse_min_out(&program_client, vault, min_out, true).await?;
+                deposit_for(
+                    &program_client,
+                    token_mint,
+                    base,
+                    partner,
+                    beneficiary,
+                    token_amount,
+                    Slippage {
+                        slippage_bps,
+                        min_out,
+                    },
+                )
+                .await?
+            }
+            UserCommand::DepositBasket {
+                weights_file,
+                token_amount,
+                partner,
+                slippage_bps,
+            } => {
+                deposit_basket(
+                    &program_client,
+                    base,
+                    partner,
+                    weights_file,
+                    token_amount,
+                    slippage_bps,
+                )
+                .await?
+            }
             UserCommand::Withdraw {
                 unmint_amount,
                 partner,
-            } => withdraw(&program_client, token_mint, base, partner, unmint_amount).await?,
+                code,
+                slippage_bps,
+                min_out,
+            } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                let vault_state: mercurial_vault::state::Vault =
+                    program_client.account(vault).await?;
+                let lp_decimals = get_mint_decimals(&program_client, vault_state.lp_mint).await?;
+                let partner_token =
+                    pda::derive_partner_token(Pubkey::from_str(&partner)?, token_mint);
+                let user = pda::derive_user(
+                    pda::derive_partner(vault, partner_token),
+                    program_client.payer(),
+                );
+                let user_lp = pda::derive_user_lp(user, vault_state.lp_mint);
+                let max_amount = get_token_balance(&program_client, user_lp).await?;
+                let unmint_amount = parse_amount(&unmint_amount, lp_decimals, Some(max_amount))?;
+                let min_out = parse_min_out(&program_client, vault, min_out, false).await?;
+                withdraw(
+                    &program_client,
+                    token_mint,
+                    base,
+                    partner,
+                    unmint_amount,
+                    Slippage {
+                        slippage_bps,
+                        min_out,
+                    },
+                )
+                .await?
+            }
             // UserCommand::WithdrawFromStrategy {
             //     unmint_amount,
             //     strategy,
@@ -179,31 +514,274 @@ async fn main() -> Result<()> {
             //         unmint_amount,
             //     )?
             // }
-            UserCommand::ViewUser { partner } => view_user(&program_client, vault, partner).await?,
+            UserCommand::ViewUser { partner, code } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                view_user(&program_client, vault, partner).await?
+            }
+            UserCommand::CloseUser { partner } => {
+                close_user(&program_client, vault, partner).await?
+            }
+            UserCommand::ViewPendingFee { partner, code } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                view_pending_fee(&program_client, vault, partner, &payer).await?
+            }
+            UserCommand::ViewRebate { partner, code } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                view_rebate(&program_client, vault, partner).await?
+            }
+            UserCommand::ClaimRebate { partner, code } => {
+                let partner = resolve_partner(&program_client, partner, code).await?;
+                claim_rebate(&program_client, vault, partner).await?
+            }
+            UserCommand::RefreshPoints { partner, owner } => {
+                refresh_points(&program_client, vault, partner, owner).await?
+            }
+            UserCommand::MeasureComputeUnits {
+                partner,
+                token_amount,
+                unmint_amount,
+            } => {
+                let vault_state: mercurial_vault::state::Vault =
+                    program_client.account(vault).await?;
+                let token_decimals = get_mint_decimals(&program_client, token_mint).await?;
+                let lp_decimals = get_mint_decimals(&program_client, vault_state.lp_mint).await?;
+                let token_amount = parse_amount(&token_amount, token_decimals, None)?;
+                let unmint_amount = parse_amount(&unmint_amount, lp_decimals, None)?;
+                measure_compute_units(
+                    &program_client,
+                    vault,
+                    partner,
+                    token_amount,
+                    unmint_amount,
+                    &payer,
+                )
+                .await?
+            }
         },
         Command::Partner(partner) => match partner {
-            PartnerCommand::InitUser { partner } => {
-                init_user(&program_client, vault, partner).await?
+            PartnerCommand::InitUser {
+                partner,
+                code,
+                partner_signer,
+            } => match (partner, code) {
+                (None, Some(code)) => {
+                    init_user_with_referral_code(&program_client, vault, code).await?
+                }
+                (partner, code) => {
+                    let partner = resolve_partner(&program_client, partner, code).await?;
+                    let partner_signer = partner_signer
+                        .map(|path| read_keypair_file(path).expect("Requires a keypair file"));
+                    init_user(&program_client, vault, partner, partner_signer).await?
+                }
+            },
+            PartnerCommand::UpdateGatedOnboarding {
+                partner,
+                require_partner_signature,
+            } => {
+                update_gated_onboarding(&program_client, vault, partner, require_partner_signature)
+                    .await?
             }
             PartnerCommand::ViewPartner { partner } => {
                 view_partner(&program_client, vault, partner).await?
             }
+            PartnerCommand::RegisterReferralCode { code, partner } => {
+                register_referral_code(&program_client, vault, code, partner, role_signer).await?
+            }
+            PartnerCommand::SetPartnerLp { partner } => {
+                set_partner_lp(&program_client, vault, partner).await?
+            }
+            PartnerCommand::VestingStatus { partner } => {
+                vesting_status(&program_client, vault, partner).await?
+            }
+            PartnerCommand::ClaimVested { partner } => {
+                claim_vested(&program_client, vault, partner).await?
+            }
+            PartnerCommand::SetPartnerProfile {
+                name,
+                website_uri,
+                logo_uri,
+                contact,
+            } => set_partner_profile(&program_client, name, website_uri, logo_uri, contact).await?,
+            PartnerCommand::UpdateRebateRatio {
+                partner,
+                rebate_ratio,
+            } => update_rebate_ratio(&program_client, vault, partner, rebate_ratio).await?,
+            PartnerCommand::FundRebateEscrow { partner, amount } => {
+                let vault_state: mercurial_vault::state::Vault =
+                    program_client.account(vault).await?;
+                let token_decimals =
+                    get_mint_decimals(&program_client, vault_state.token_mint).await?;
+                let amount = parse_amount(&amount, token_decimals, None)?;
+                fund_rebate_escrow(&program_client, vault, partner, amount).await?
+            }
+            PartnerCommand::UpdatePointsMultiplier {
+                partner,
+                points_multiplier,
+            } => {
+                update_points_multiplier(&program_client, vault, partner, points_multiplier).await?
+            }
+            PartnerCommand::PointsLeaderboard { partner } => {
+                points_leaderboard(&program_client, vault, partner).await?
+            }
+            PartnerCommand::InitCampaign {
+                partner,
+                campaign_id,
+            } => init_campaign(&program_client, vault, partner, campaign_id).await?,
+            PartnerCommand::ViewCampaign {
+                partner,
+                campaign_id,
+            } => view_campaign(&program_client, vault, partner, campaign_id).await?,
+            PartnerCommand::PartnerSummary { partner } => {
+                partner_summary(&program_client, vault, partner, &payer).await?
+            }
         },
         Command::Admin(admin) => match admin {
             AdminCommand::InitPartner { partner } => {
-                init_partner(&program_client, vault, partner).await?
+                init_partner(&program_client, vault, partner, role_signer).await?
             }
             AdminCommand::InitPartnerAllVault { partner } => {
-                init_partner_all_vault(&program_client, partner).await?
+                init_partner_all_vault(&program_client, partner, role_signer).await?
             }
             AdminCommand::UpdateFeeRatio { partner, fee_ratio } => {
-                update_fee_ratio(&program_client, vault, partner, fee_ratio).await?
+                update_fee_ratio(&program_client, vault, partner, fee_ratio, role_signer).await?
+            }
+            AdminCommand::UpdateUserFeeRatio {
+                partner,
+                owner,
+                fee_ratio,
+            } => update_user_fee_ratio(&program_client, vault, partner, owner, fee_ratio).await?,
+            AdminCommand::FundPartner {
+                partner,
+                amount,
+                reference,
+            } => {
+                let token_decimals = get_mint_decimals(&program_client, token_mint).await?;
+                let amount = parse_amount(&amount, token_decimals, None)?;
+                fund_partner(
+                    &program_client,
+                    vault,
+                    partner,
+                    amount,
+                    reference,
+                    role_signer,
+                )
+                .await?
+            }
+            AdminCommand::FundPartnerLp {
+                partner,
+                lp_amount,
+                reference,
+            } => {
+                let vault_state: mercurial_vault::state::Vault =
+                    program_client.account(vault).await?;
+                let lp_decimals = get_mint_decimals(&program_client, vault_state.lp_mint).await?;
+                let lp_amount = parse_amount(&lp_amount, lp_decimals, None)?;
+                fund_partner_lp(
+                    &program_client,
+                    vault,
+                    partner,
+                    lp_amount,
+                    reference,
+                    role_signer,
+                )
+                .await?
+            }
+            AdminCommand::FundPartnerVesting {
+                partner,
+                amount,
+                cliff_duration,
+                duration,
+                reference,
+            } => {
+                let token_decimals = get_mint_decimals(&program_client, token_mint).await?;
+                let amount = parse_amount(&amount, token_decimals, None)?;
+                fund_partner_vesting(
+                    &program_client,
+                    vault,
+                    partner,
+                    amount,
+                    cliff_duration,
+                    duration,
+                    reference,
+                    role_signer,
+                )
+                .await?
+            }
+            AdminCommand::PayoutStatement { partner } => {
+                payout_statement(&program_client, vault, partner).await?
+            }
+            AdminCommand::InitConfig {} => init_config(&program_client).await?,
+            AdminCommand::SetFunders { treasury, funders } => {
+                set_funders(&program_client, treasury, funders).await?
             }
-            AdminCommand::FundPartner { partner, amount } => {
-                fund_partner(&program_client, vault, partner, amount).await?
+            AdminCommand::ViewConfig {} => view_config(&program_client).await?,
+            AdminCommand::MigratePartner { partner } => {
+                migrate_partner(&program_client, vault, partner).await?
+            }
+            AdminCommand::UpdateGroupFeeRatio { partner, fee_ratio } => {
+                update_group_fee_ratio(&program_client, partner, fee_ratio, role_signer).await?
+            }
+            AdminCommand::ViewPartnerGroup { partner } => {
+                view_partner_group(&program_client, partner).await?
+            }
+            AdminCommand::SetRoles {
+                partner_manager,
+                fee_manager,
+                pauser,
+            } => set_roles(&program_client, partner_manager, fee_manager, pauser).await?,
+            AdminCommand::PausePartner { partner, paused } => {
+                pause_partner(&program_client, vault, partner, paused, role_signer).await?
+            }
+            AdminCommand::ClosePartner { partner } => {
+                close_partner(&program_client, vault, partner, role_signer).await?
+            }
+            AdminCommand::BackfillPartnerUsers { partner } => {
+                backfill_partner_users(&program_client, vault, partner, role_signer).await?
+            }
+            AdminCommand::ReassignReferralCode { code, partner } => {
+                reassign_referral_code(&program_client, vault, code, partner, role_signer).await?
+            }
+            AdminCommand::CloseReferralCode { code } => {
+                close_referral_code(&program_client, code, role_signer).await?
             }
         },
     };
 
     Ok(())
 }
+
+// max deposit is token balance of payer, native SOL is not supported as lamports also pay fee
+async fn get_deposit_max_amount<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    token_mint: Pubkey,
+) -> Result<Option<u64>> {
+    if token_mint == spl_token::native_mint::id() {
+        return Ok(None);
+    }
+    let user_token = spl_associated_token_account::get_associated_token_address(
+        &program_client.payer(),
+        &token_mint,
+    );
+    Ok(Some(get_token_balance(program_client, user_token).await?))
+}
+
+// min out of deposit is in lp decimals, min out of withdraw is in token decimals
+async fn parse_min_out<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    min_out: Option<String>,
+    is_deposit: bool,
+) -> Result<Option<u64>> {
+    let min_out = match min_out {
+        Some(min_out) => min_out,
+        None => return Ok(None),
+    };
+    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
+    let mint = if is_deposit {
+        vault_state.lp_mint
+    } else {
+        vault_state.token_mint
+    };
+    let decimals = get_mint_decimals(program_client, mint).await?;
+    Ok(Some(parse_amount(&min_out, decimals, None)?))
+}
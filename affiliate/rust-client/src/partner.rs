@@ -1,44 +1,71 @@
-use crate::utils::get_or_create_ata;
-use anyhow::Result;
-use solana_program::sysvar;
+use crate::utils::{self, get_or_create_ata};
+use affiliate_sdk::accounts::{fetch_clock_time, fetch_partner, fetch_partner_users, fetch_vault};
+use affiliate_sdk::{instructions, pda};
+use anyhow::{anyhow, Context, Result};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
-use solana_sdk::system_program;
 use std::ops::Deref;
 use std::str::FromStr;
-// must be called by user
+// must be called by user, partner_signer is required when partner gates onboarding
 pub async fn init_user<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
     partner: String,
+    partner_signer: Option<Keypair>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner_token = get_or_create_ata(program_client, vault.token_mint, partner_wallet).await?;
+    let partner = pda::derive_partner(vault.vault, partner_token);
+    println!("{} {}", partner, partner_token);
+    // return Ok(());
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::init_user(
+            &vault,
+            partner_wallet,
+            program_client.payer(),
+            partner_signer.as_ref().map(|signer| signer.pubkey()),
+        ));
+    let builder = match partner_signer.as_ref() {
+        Some(signer) => builder.signer(signer),
+        None => builder,
+    };
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn init_user_permissionless<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
 ) -> Result<()> {
     let partner = Pubkey::from_str(&partner).unwrap();
 
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
     let token_mint = vault_state.token_mint;
     let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
+    let partner = pda::derive_partner(vault, partner_token);
     println!("{} {}", partner, partner_token);
     // return Ok(());
-    // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
-
-    let (user, _nonce) = Pubkey::find_program_address(
-        &[partner.as_ref(), program_client.payer().as_ref()],
-        &affiliate::id(),
-    );
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
 
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::InitUser {
-            user,
+        .instruction(instructions::init_user_permissionless(
             partner,
-            owner: program_client.payer(),
-            system_program: system_program::id(),
-            rent: sysvar::rent::ID,
-        })
-        .args(affiliate::instruction::InitUser {});
+            program_client.payer(),
+            program_client.payer(),
+        ));
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -46,38 +73,456 @@ pub async fn init_user<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
-pub async fn init_user_permissionless<C: Deref<Target = impl Signer> + Clone>(
+// must be called by user
+pub async fn init_user_with_referral_code<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
+    code: String,
+) -> Result<()> {
+    let referral_code = pda::derive_referral_code(&code);
+    let referral_code_state: affiliate::ReferralCode =
+        program_client.account(referral_code).await?;
+
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner =
+        utils::get_partner(program_client, &vault, referral_code_state.partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::init_user_with_referral_code(
+            &vault,
+            code,
+            referral_code_state.partner_wallet,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// must be called by partner wallet, admin or partner manager, wallet must be a partner of the vault
+pub async fn register_referral_code<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    code: String,
     partner: String,
+    role_signer: Option<&Keypair>,
 ) -> Result<()> {
-    let partner = Pubkey::from_str(&partner).unwrap();
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    utils::migrate_partner_if_needed(program_client, partner).await?;
 
+    let builder = program_client
+        .request()
+        .instruction(instructions::register_referral_code(
+            &vault,
+            code,
+            partner_wallet,
+            utils::get_authority(program_client, role_signer),
+            role_signer.is_some(),
+        ));
+    let builder = utils::with_role_signer(builder, role_signer);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// must be called by partner wallet or admin
+pub async fn update_gated_onboarding<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    require_partner_signature: bool,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::update_gated_onboarding(
+            &vault,
+            partner_wallet,
+            require_partner_signature,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// must be called by partner wallet or admin, partner receives fee in vault lp afterward
+pub async fn set_partner_lp<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    get_or_create_ata(program_client, vault.lp_mint, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::set_partner_lp(
+            &vault,
+            partner_wallet,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+async fn get_partner_vestings<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+    vesting_count: u64,
+) -> Result<Vec<(Pubkey, affiliate::PartnerVesting)>> {
+    let mut vestings = vec![];
+    for index in 0..vesting_count {
+        let partner_vesting = pda::derive_partner_vesting(partner, index);
+        let vesting_state: affiliate::PartnerVesting =
+            program_client.account(partner_vesting).await?;
+        vestings.push((partner_vesting, vesting_state));
+    }
+    Ok(vestings)
+}
+
+pub async fn vesting_status<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner = Pubkey::from_str(&partner).unwrap();
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
     let token_mint = vault_state.token_mint;
     let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-    println!("{} {}", partner, partner_token);
-    // return Ok(());
+    let partner = pda::derive_partner(vault, partner_token);
     // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
+    let partner_state = fetch_partner(program_client, partner).await?;
+
+    let current_time = fetch_clock_time(program_client).await?;
+    for (partner_vesting, vesting_state) in
+        get_partner_vestings(program_client, partner, partner_state.vesting_count).await?
+    {
+        println!(
+            "vesting {} total {} claimed {} vested {} claimable {} start {} cliff {} duration {}",
+            partner_vesting,
+            vesting_state.total_amount,
+            vesting_state.claimed_amount,
+            vesting_state
+                .get_vested_amount(current_time)
+                .context("vested amount overflows")?,
+            vesting_state
+                .get_claimable_amount(current_time)
+                .context("claimable amount overflows")?,
+            vesting_state.start_at,
+            vesting_state.cliff_duration,
+            vesting_state.duration
+        );
+    }
+    Ok(())
+}
+
+// can be called by anyone, vested fee is sent to partner token
+pub async fn claim_vested<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = fetch_partner(program_client, partner).await?;
+
+    let current_time = fetch_clock_time(program_client).await?;
+    for (partner_vesting, vesting_state) in
+        get_partner_vestings(program_client, partner, partner_state.vesting_count).await?
+    {
+        if vesting_state
+            .get_claimable_amount(current_time)
+            .unwrap_or(0)
+            == 0
+        {
+            continue;
+        }
+        let builder = program_client
+            .request()
+            .instruction(instructions::claim_vested(
+                &vault,
+                partner_wallet,
+                partner_vesting,
+                vesting_state.escrow,
+            ));
+
+        let signature = builder.send().await?;
+        println!("claim vesting {} {}", partner_vesting, signature);
+    }
+    Ok(())
+}
 
-    let (user, _nonce) = Pubkey::find_program_address(
-        &[partner.as_ref(), program_client.payer().as_ref()],
-        &affiliate::id(),
+pub async fn view_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
+    let token_mint = vault_state.token_mint;
+    let partner_token = get_or_create_ata(program_client, token_mint, partner_wallet).await?;
+    let partner = pda::derive_partner(vault, partner_token);
+    // check whether partner is existed
+    let partner_state = fetch_partner(program_client, partner).await?;
+    println!("{:?}", partner_state);
+
+    let token_decimals = utils::get_mint_decimals(program_client, token_mint).await?;
+    let lp_decimals = utils::get_mint_decimals(program_client, vault_state.lp_mint).await?;
+    println!(
+        "total lp {}",
+        utils::format_amount(partner_state.total_lp, lp_decimals)
+    );
+    for (name, amount) in [
+        ("outstanding fee", u128::from(partner_state.outstanding_fee)),
+        ("spilled fee", u128::from(partner_state.spilled_fee)),
+        ("cumulative fee", partner_state.cumulative_fee()),
+        ("total deposited", partner_state.total_deposited()),
+        ("total withdrawn", partner_state.total_withdrawn()),
+        (
+            "outstanding rebate",
+            u128::from(partner_state.outstanding_rebate),
+        ),
+    ] {
+        println!("{} {}", name, utils::format_amount(amount, token_decimals));
+    }
+    if partner_state.spilled_fee > 0 {
+        println!(
+            "outstanding fee is saturated, spilled fee {} is moved to outstanding fee after payout",
+            partner_state.spilled_fee
+        );
+    }
+    if partner_state.cumulative_fee() == u128::MAX {
+        println!("cumulative fee is saturated");
+    }
+
+    let partner_profile = pda::derive_partner_profile(partner_wallet);
+    match program_client
+        .account::<affiliate::PartnerProfile>(partner_profile)
+        .await
+    {
+        Ok(profile) => println!("{:?}", profile),
+        Err(_) => println!("partner profile {} is not created", partner_profile),
+    }
+    Ok(())
+}
+
+// must be called by partner wallet, create profile if it is not existed
+pub async fn set_partner_profile<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    name: String,
+    website_uri: String,
+    logo_uri: String,
+    contact: String,
+) -> Result<()> {
+    let partner_wallet = program_client.payer();
+    let partner_profile = pda::derive_partner_profile(partner_wallet);
+
+    let instruction = if program_client
+        .rpc()
+        .get_account_data(&partner_profile)
+        .is_err()
+    {
+        instructions::init_partner_profile(partner_wallet, name, website_uri, logo_uri, contact)
+    } else {
+        instructions::update_partner_profile(partner_wallet, name, website_uri, logo_uri, contact)
+    };
+    let builder = program_client.request().instruction(instruction);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// must be called by partner wallet or admin
+pub async fn update_rebate_ratio<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    rebate_ratio: u64,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::update_rebate_ratio(
+            &vault,
+            partner_wallet,
+            rebate_ratio,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// can be called by anyone, rebate escrow is created if missing
+pub async fn fund_rebate_escrow<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    amount: u64,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    let partner_state = fetch_partner(program_client, partner).await?;
+
+    let rebate_escrow = pda::derive_rebate_escrow(partner);
+    let rpc_client = program_client.rpc();
+    if rpc_client.get_account_data(&rebate_escrow).is_err() {
+        let builder = program_client
+            .request()
+            .instruction(instructions::init_rebate_escrow(
+                &vault,
+                partner_wallet,
+                program_client.payer(),
+            ));
+
+        let signature = builder.send().await?;
+        println!("create rebate escrow {}", signature);
+    }
+
+    let funder_token =
+        get_or_create_ata(program_client, vault.token_mint, program_client.payer()).await?;
+    let builder = program_client
+        .request()
+        .instruction(spl_token::instruction::transfer(
+            &spl_token::id(),
+            &funder_token,
+            &rebate_escrow,
+            &program_client.payer(),
+            &[],
+            amount,
+        )?);
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+    println!(
+        "outstanding rebate {} escrow {}",
+        partner_state.outstanding_rebate, rebate_escrow
     );
 
+    Ok(())
+}
+
+// must be called by partner wallet or admin, 10_000 is 1x
+pub async fn update_points_multiplier<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    points_multiplier: u64,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::InitUserPermissionless {
+        .instruction(instructions::update_points_multiplier(
+            &vault,
+            partner_wallet,
+            points_multiplier,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub async fn points_leaderboard<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner = Pubkey::from_str(&partner).unwrap();
+    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
+    let token_mint = vault_state.token_mint;
+    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
+    let partner = pda::derive_partner(vault, partner_token);
+    let partner_state = fetch_partner(program_client, partner).await?;
+
+    let mut users = fetch_partner_users(program_client, partner).await?;
+    // points are only accrued up to last update of each user
+    users.sort_by(|a, b| b.1.points().cmp(&a.1.points()));
+
+    println!(
+        "points multiplier {}",
+        partner_state.get_points_multiplier()
+    );
+    for (rank, (user, user_state, _)) in users.iter().enumerate() {
+        println!(
+            "{} user {} points {} updated at {}",
+            rank + 1,
             user,
-            partner,
-            owner: program_client.payer(),
-            payer: program_client.payer(),
-            system_program: system_program::id(),
-        })
-        .args(affiliate::instruction::InitUserPermissionless {});
+            user_state.points(),
+            user_state.last_points_update_at()
+        );
+    }
+
+    Ok(())
+}
+
+// must be called by partner wallet or admin
+pub async fn init_campaign<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    campaign_id: u32,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+    // check whether partner is existed and migrate it to zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+
+    let builder = program_client
+        .request()
+        .instruction(instructions::init_campaign(
+            &vault,
+            partner_wallet,
+            campaign_id,
+            program_client.payer(),
+        ));
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -85,19 +530,91 @@ pub async fn init_user_permissionless<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
-pub async fn view_partner<C: Deref<Target = impl Signer> + Clone>(
+pub async fn view_campaign<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
     partner: String,
+    campaign_id: u32,
 ) -> Result<()> {
     let partner = Pubkey::from_str(&partner).unwrap();
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
     let token_mint = vault_state.token_mint;
     let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-    // check whether partner is existed
-    let partner_state: affiliate::Partner = program_client.account(partner).await?;
-    println!("{:?}", partner_state);
+    let partner = pda::derive_partner(vault, partner_token);
+
+    let campaign_state: affiliate::Campaign = program_client
+        .account(pda::derive_campaign(partner, campaign_id))
+        .await?;
+    println!("{:?}", campaign_state);
+
+    Ok(())
+}
+
+// users passed in one simulation, limited by transaction size
+const SUMMARY_USER_CHUNK: usize = 20;
+
+pub async fn partner_summary<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    payer: &Keypair,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = utils::get_partner(program_client, &vault, partner_wallet).await?;
+
+    // summary loads partner and users in zero-copy layout
+    utils::migrate_partner_if_needed(program_client, partner).await?;
+    utils::migrate_partner_users(program_client, partner).await?;
+
+    let users = fetch_partner_users(program_client, partner).await?;
+    let user_keys: Vec<Pubkey> = users.iter().map(|(user, _, _)| *user).collect();
+
+    let mut summary: Option<affiliate::PartnerSummary> = None;
+    let mut pending_fee = 0u64;
+    // simulate once without users when partner has no user
+    let chunks: Vec<&[Pubkey]> = if user_keys.is_empty() {
+        vec![&[]]
+    } else {
+        user_keys.chunks(SUMMARY_USER_CHUNK).collect()
+    };
+    for chunk in chunks {
+        let builder = program_client
+            .request()
+            .instruction(instructions::get_partner_summary(
+                &vault,
+                partner_wallet,
+                chunk,
+            ));
+
+        let simulation = utils::simulate_transaction(&builder, program_client, &vec![payer])
+            .map_err(|err| anyhow!("{}", err))?;
+        let logs = simulation
+            .value
+            .logs
+            .context("No log in simulation found")?;
+        let chunk_summary: affiliate::PartnerSummary =
+            utils::parse_event_log(&logs).context("Event log not found")?;
+        pending_fee += chunk_summary.pending_fee;
+        summary = Some(chunk_summary);
+    }
+
+    let summary = summary.context("Partner summary not found")?;
+    println!(
+        "virtual price {} fee ratio {} users {} total lp {} total amount {}",
+        summary.virtual_price,
+        summary.fee_ratio,
+        summary.user_count,
+        summary.total_lp,
+        summary.total_amount
+    );
+    println!(
+        "outstanding fee {} cumulative fee {} pending fee {} ({} users)",
+        summary.outstanding_fee,
+        summary.cumulative_fee,
+        pending_fee,
+        user_keys.len()
+    );
+
     Ok(())
 }
@@ -0,0 +1,145 @@
+//! PDA derivation of vault and affiliate accounts
+
+use solana_program::pubkey::Pubkey;
+
+/// vault of token mint, base is the vault base key
+pub fn derive_vault(token_mint: Pubkey, base: Pubkey) -> Pubkey {
+    let (vault, _nonce) = Pubkey::find_program_address(
+        &[b"vault".as_ref(), token_mint.as_ref(), base.as_ref()],
+        &mercurial_vault::id(),
+    );
+    vault
+}
+
+/// token vault holding liquidity of vault
+pub fn derive_token_vault(vault: Pubkey) -> Pubkey {
+    let (token_vault, _nonce) = Pubkey::find_program_address(
+        &[b"token_vault".as_ref(), vault.as_ref()],
+        &mercurial_vault::id(),
+    );
+    token_vault
+}
+
+/// partner token, associated token account of partner wallet receiving fee
+pub fn derive_partner_token(partner_wallet: Pubkey, token_mint: Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(&partner_wallet, &token_mint)
+}
+
+/// partner PDA of partner token in vault
+pub fn derive_partner(vault: Pubkey, partner_token: Pubkey) -> Pubkey {
+    let (partner, _nonce) =
+        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
+    partner
+}
+
+/// user PDA of owner under partner
+pub fn derive_user(partner: Pubkey, owner: Pubkey) -> Pubkey {
+    let (user, _nonce) =
+        Pubkey::find_program_address(&[partner.as_ref(), owner.as_ref()], &affiliate::id());
+    user
+}
+
+/// lp token account of user PDA, lp is kept in user PDA to track partner fee
+pub fn derive_user_lp(user: Pubkey, lp_mint: Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(&user, &lp_mint)
+}
+
+/// temporary wsol account of user, used to wrap and unwrap native SOL
+pub fn derive_temp_wsol(user: Pubkey) -> Pubkey {
+    let (temp_wsol, _nonce) = Pubkey::find_program_address(
+        &[affiliate::TEMP_WSOL_PREFIX, user.as_ref()],
+        &affiliate::id(),
+    );
+    temp_wsol
+}
+
+/// config PDA holding admin roles
+pub fn derive_config() -> Pubkey {
+    let (config, _nonce) =
+        Pubkey::find_program_address(&[affiliate::CONFIG_PREFIX], &affiliate::id());
+    config
+}
+
+/// payout receipt of partner at index
+pub fn derive_payout_receipt(partner: Pubkey, index: u64) -> Pubkey {
+    let (payout_receipt, _nonce) = Pubkey::find_program_address(
+        &[
+            affiliate::PAYOUT_RECEIPT_PREFIX,
+            partner.as_ref(),
+            index.to_le_bytes().as_ref(),
+        ],
+        &affiliate::id(),
+    );
+    payout_receipt
+}
+
+/// partner group of partner wallet, linking partners of the wallet across vaults
+pub fn derive_partner_group(partner_wallet: Pubkey) -> Pubkey {
+    let (partner_group, _nonce) = Pubkey::find_program_address(
+        &[affiliate::PARTNER_GROUP_PREFIX, partner_wallet.as_ref()],
+        &affiliate::id(),
+    );
+    partner_group
+}
+
+/// vesting of partner at index
+pub fn derive_partner_vesting(partner: Pubkey, index: u64) -> Pubkey {
+    let (partner_vesting, _nonce) = Pubkey::find_program_address(
+        &[
+            affiliate::PARTNER_VESTING_PREFIX,
+            partner.as_ref(),
+            index.to_le_bytes().as_ref(),
+        ],
+        &affiliate::id(),
+    );
+    partner_vesting
+}
+
+/// escrow holding unvested fee of partner vesting
+pub fn derive_vesting_escrow(partner_vesting: Pubkey) -> Pubkey {
+    let (escrow, _nonce) = Pubkey::find_program_address(
+        &[affiliate::VESTING_ESCROW_PREFIX, partner_vesting.as_ref()],
+        &affiliate::id(),
+    );
+    escrow
+}
+
+/// rebate escrow of partner, funded by partner and claimed by users
+pub fn derive_rebate_escrow(partner: Pubkey) -> Pubkey {
+    let (rebate_escrow, _nonce) = Pubkey::find_program_address(
+        &[affiliate::REBATE_ESCROW_PREFIX, partner.as_ref()],
+        &affiliate::id(),
+    );
+    rebate_escrow
+}
+
+/// campaign of partner
+pub fn derive_campaign(partner: Pubkey, campaign_id: u32) -> Pubkey {
+    let (campaign, _nonce) = Pubkey::find_program_address(
+        &[
+            affiliate::CAMPAIGN_PREFIX,
+            partner.as_ref(),
+            campaign_id.to_le_bytes().as_ref(),
+        ],
+        &affiliate::id(),
+    );
+    campaign
+}
+
+/// profile of partner wallet
+pub fn derive_partner_profile(partner_wallet: Pubkey) -> Pubkey {
+    let (partner_profile, _nonce) = Pubkey::find_program_address(
+        &[affiliate::PARTNER_PROFILE_PREFIX, partner_wallet.as_ref()],
+        &affiliate::id(),
+    );
+    partner_profile
+}
+
+/// referral code resolved to partner wallet
+pub fn derive_referral_code(code: &str) -> Pubkey {
+    let (referral_code, _nonce) = Pubkey::find_program_address(
+        &[affiliate::REFERRAL_CODE_PREFIX, code.as_bytes()],
+        &affiliate::id(),
+    );
+    referral_code
+}
@@ -0,0 +1,70 @@
+//! Structured results returned by the sdk
+
+use solana_program::pubkey::Pubkey;
+
+/// vault with its token accounts and mint decimals
+#[derive(Debug, Clone)]
+pub struct VaultInfo {
+    /// vault address
+    pub vault: Pubkey,
+    /// token vault holding liquidity of vault
+    pub token_vault: Pubkey,
+    /// token mint of vault
+    pub token_mint: Pubkey,
+    /// lp mint of vault
+    pub lp_mint: Pubkey,
+    /// decimals of token mint
+    pub token_decimals: u8,
+    /// decimals of lp mint
+    pub lp_decimals: u8,
+    /// vault state
+    pub state: mercurial_vault::state::Vault,
+}
+
+impl VaultInfo {
+    /// whether vault token is native SOL, which is wrapped and unwrapped by the program
+    pub fn is_native(&self) -> bool {
+        self.token_mint == spl_token::native_mint::id()
+    }
+}
+
+/// position of user in vault through partner
+#[derive(Debug, Clone)]
+pub struct UserPosition {
+    /// user PDA
+    pub user: Pubkey,
+    /// partner PDA
+    pub partner: Pubkey,
+    /// lp token held in user PDA
+    pub lp_token: u64,
+    /// current virtual price of vault
+    pub virtual_price: u64,
+    /// lp token valued in native token at virtual price
+    pub amount: u128,
+    /// fee ratio applied to user, override or partner fee ratio
+    pub fee_ratio: u64,
+    /// user state
+    pub state: affiliate::UserV2,
+}
+
+/// minimum amount out of deposit/withdraw, explicit min_out overrides slippage_bps
+#[derive(Debug, Clone, Copy)]
+pub struct Slippage {
+    /// max slippage from amount expected at current virtual price, in bps
+    pub slippage_bps: u64,
+    /// explicit minimum amount out
+    pub min_out: Option<u64>,
+}
+
+/// one leg of a basket deposit, token of owner is deposited to vault through partner wallet
+#[derive(Debug, Clone, Copy)]
+pub struct BasketLeg<'a> {
+    /// vault of the leg
+    pub vault: &'a VaultInfo,
+    /// partner wallet that the leg is deposited through
+    pub partner_wallet: Pubkey,
+    /// token amount deposited to vault
+    pub token_amount: u64,
+    /// minimum lp token amount minted by vault
+    pub minimum_lp_token_amount: u64,
+}
@@ -1,88 +1,210 @@
-use anyhow::Result;
+use crate::utils::{self, get_or_create_ata};
+use affiliate_sdk::accounts::{
+    fetch_migrate_instructions, fetch_partner, fetch_partner_needs_migration, fetch_user,
+    fetch_user_needs_migration, fetch_user_position, fetch_vault, fetch_virtual_price,
+};
+use affiliate_sdk::amount::{get_expected_amount, get_expected_lp};
+use affiliate_sdk::instructions;
+use affiliate_sdk::pda;
+use affiliate_sdk::types::{BasketLeg, Slippage, VaultInfo};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
 use solana_program::program_pack::Pack;
 use solana_program::pubkey::Pubkey;
-use solana_program::sysvar;
 use solana_sdk::signature::Signer;
 use solana_sdk::signer::keypair::Keypair;
 use solana_sdk::system_instruction;
-use solana_sdk::system_program;
-use spl_associated_token_account;
+use std::convert::TryFrom;
 use std::ops::Deref;
 use std::str::FromStr;
 
+// migrate partner and user still in borsh layout and create user PDA of owner if it is not
+// existed, payer funds the accounts
+async fn init_user_if_needed<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+    owner: Pubkey,
+) -> Result<Pubkey> {
+    // check whether partner is existed
+    let _partner_state = fetch_partner(program_client, partner).await?;
+    let user = pda::derive_user(partner, owner);
+    let mut builder = program_client.request();
+    let mut has_instruction = false;
+    for instruction in fetch_migrate_instructions(program_client, partner, Some(user)).await? {
+        builder = builder.instruction(instruction);
+        has_instruction = true;
+    }
+    if has_instruction {
+        println!("migrate partner {} user {}", partner, user);
+    }
+    if fetch_user(program_client, user).await?.is_none() {
+        println!("create user {}", user);
+        builder = builder.instruction(instructions::init_user_permissionless(
+            partner,
+            owner,
+            program_client.payer(),
+        ));
+        has_instruction = true;
+    }
+    if has_instruction {
+        let signature = builder.send().await?;
+        println!("{}", signature);
+    }
+    Ok(user)
+}
+
+fn get_partner(vault: &VaultInfo, partner_wallet: Pubkey) -> Pubkey {
+    pda::derive_partner(
+        vault.vault,
+        pda::derive_partner_token(partner_wallet, vault.token_mint),
+    )
+}
+
 pub async fn deposit<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     token_mint: Pubkey,
     base: Pubkey,
     partner: String,
     token_amount: u64,
+    campaign_id: Option<u32>,
+    slippage: Slippage,
 ) -> Result<()> {
     println!("deposit {} partner {}", token_amount, partner);
-    let partner = Pubkey::from_str(&partner).unwrap();
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, pda::derive_vault(token_mint, base)).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = init_user_if_needed(program_client, partner, program_client.payer()).await?;
+    get_or_create_ata(program_client, vault.lp_mint, user).await?;
+    // native SOL is wrapped by the program
+    if !vault.is_native() {
+        get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    }
+    let minimum_lp_token_amount =
+        get_minimum_lp_token_amount(program_client, &vault, token_amount, slippage).await?;
 
-    let (vault, _vault_bump) = Pubkey::find_program_address(
-        &[b"vault".as_ref(), token_mint.as_ref(), base.as_ref()],
-        &mercurial_vault::id(),
-    );
+    let builder = program_client.request().instruction(instructions::deposit(
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+        token_amount,
+        minimum_lp_token_amount,
+        campaign_id,
+    ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
 
-    let (token_vault, _token_vault_bump) = Pubkey::find_program_address(
-        &[b"token_vault".as_ref(), vault.as_ref()],
-        &mercurial_vault::id(),
+// payer deposits on behalf of beneficiary, lp position is credited to beneficiary
+pub async fn deposit_for<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    token_mint: Pubkey,
+    base: Pubkey,
+    partner: String,
+    beneficiary: String,
+    token_amount: u64,
+    slippage: Slippage,
+) -> Result<()> {
+    println!(
+        "deposit {} for {} partner {}",
+        token_amount, beneficiary, partner
     );
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let beneficiary = Pubkey::from_str(&beneficiary).unwrap();
+    let vault = fetch_vault(program_client, pda::derive_vault(token_mint, base)).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = init_user_if_needed(program_client, partner, beneficiary).await?;
+    get_or_create_ata(program_client, vault.lp_mint, user).await?;
+    get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    let minimum_lp_token_amount =
+        get_minimum_lp_token_amount(program_client, &vault, token_amount, slippage).await?;
 
-    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
-    let lp_mint = vault_state.lp_mint;
+    let builder = program_client
+        .request()
+        .instruction(instructions::deposit_for(
+            &vault,
+            partner_wallet,
+            beneficiary,
+            program_client.payer(),
+            token_amount,
+            minimum_lp_token_amount,
+        ));
 
-    let user_token = get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    let signature = builder.send().await?;
+    println!("{}", signature);
 
-    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-    // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
-    let (user, _nonce) = Pubkey::find_program_address(
-        &[partner.as_ref(), program_client.payer().as_ref()],
-        &affiliate::id(),
-    );
-    // check whether user is existed
-    let rpc_client = program_client.rpc();
-    if rpc_client.get_account_data(&user).is_err() {
-        // create user account
-        let builder = program_client
-            .request()
-            .accounts(affiliate::accounts::InitUserPermissionless {
-                user,
-                partner,
-                owner: program_client.payer(),
-                payer: program_client.payer(),
-                system_program: system_program::id(),
-            })
-            .args(affiliate::instruction::InitUserPermissionless {});
+    Ok(())
+}
 
-        let signature = builder.send().await?;
-        println!("create user {}", signature);
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BasketWeight {
+    pub token_mint: String,
+    pub weight: u64,
+}
+
+// weights file is a json list of token mint and weight, token amount is split by weight,
+// so every leg must have the same decimals
+pub async fn deposit_basket<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    base: Pubkey,
+    partner: String,
+    weights_file: String,
+    token_amount: String,
+    slippage_bps: u64,
+) -> Result<()> {
+    let weights: Vec<BasketWeight> = serde_json::from_str(&std::fs::read_to_string(weights_file)?)?;
+    let mut vaults = vec![];
+    for leg in weights.iter() {
+        let token_mint = Pubkey::from_str(&leg.token_mint)?;
+        vaults.push(fetch_vault(program_client, pda::derive_vault(token_mint, base)).await?);
     }
+    let decimals = vaults
+        .first()
+        .ok_or_else(|| anyhow!("weights file is empty"))?
+        .token_decimals;
+    if vaults.iter().any(|vault| vault.token_decimals != decimals) {
+        return Err(anyhow!(
+            "basket legs have different decimals, deposit each leg separately"
+        ));
+    }
+    let token_amount = utils::parse_amount(&token_amount, decimals, None)?;
+    let total_weight: u64 = weights.iter().map(|leg| leg.weight).sum();
+    if total_weight == 0 {
+        return Err(anyhow!("total weight of basket is zero"));
+    }
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let slippage = Slippage {
+        slippage_bps,
+        min_out: None,
+    };
+
+    let mut legs = vec![];
+    for (leg, vault) in weights.iter().zip(vaults.iter()) {
+        let leg_amount =
+            u64::try_from(token_amount as u128 * leg.weight as u128 / total_weight as u128)?;
+        println!("deposit {} mint {}", leg_amount, vault.token_mint);
 
-    let user_lp = get_or_create_ata(program_client, lp_mint, user).await?;
+        get_or_create_ata(program_client, vault.token_mint, program_client.payer()).await?;
+        let partner = get_partner(vault, partner_wallet);
+        let user = init_user_if_needed(program_client, partner, program_client.payer()).await?;
+        get_or_create_ata(program_client, vault.lp_mint, user).await?;
+        let minimum_lp_token_amount =
+            get_minimum_lp_token_amount(program_client, vault, leg_amount, slippage).await?;
+
+        legs.push(BasketLeg {
+            vault,
+            partner_wallet,
+            token_amount: leg_amount,
+            minimum_lp_token_amount,
+        });
+    }
 
     let builder = program_client
         .request()
-        .accounts(affiliate::accounts::DepositWithdrawLiquidity {
-            partner,
-            user,
-            vault: vault,
-            vault_program: mercurial_vault::id(),
-            token_vault: token_vault,
-            vault_lp_mint: lp_mint,
-            user_token,
-            user_lp,
-            owner: program_client.payer(),
-            token_program: spl_token::id(),
-        })
-        .args(mercurial_vault::instruction::Deposit {
-            token_amount,
-            minimum_lp_token_amount: 0,
-        });
+        .instruction(instructions::deposit_basket(&legs, program_client.payer()));
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -96,75 +218,265 @@ pub async fn withdraw<C: Deref<Target = impl Signer> + Clone>(
     base: Pubkey,
     partner: String,
     unmint_amount: u64,
+    slippage: Slippage,
 ) -> Result<()> {
     println!("withdraw {} lp token partner {}", unmint_amount, partner);
-    let partner = Pubkey::from_str(&partner).unwrap();
-    let (vault, _vault_bump) = Pubkey::find_program_address(
-        &[b"vault".as_ref(), token_mint.as_ref(), base.as_ref()],
-        &mercurial_vault::id(),
-    );
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, pda::derive_vault(token_mint, base)).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = init_user_if_needed(program_client, partner, program_client.payer()).await?;
+    get_or_create_ata(program_client, vault.lp_mint, user).await?;
+    // native SOL is unwrapped by the program
+    if !vault.is_native() {
+        get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    }
+    // attribute fee to last-touch campaign of user
+    let last_campaign_id = fetch_user(program_client, user)
+        .await?
+        .ok_or_else(|| anyhow!("user is not created"))?
+        .last_campaign_id();
+    let min_out_amount =
+        get_min_out_amount(program_client, &vault, unmint_amount, slippage).await?;
 
-    let (token_vault, _token_vault_bump) = Pubkey::find_program_address(
-        &[b"token_vault".as_ref(), vault.as_ref()],
-        &mercurial_vault::id(),
-    );
+    let builder = program_client.request().instruction(instructions::withdraw(
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+        unmint_amount,
+        min_out_amount,
+        last_campaign_id,
+    ));
 
-    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
-    let lp_mint = vault_state.lp_mint;
+    let signature = builder.send().await?;
+    println!("{}", signature);
 
-    let user_token = get_or_create_ata(program_client, token_mint, program_client.payer()).await?;
+    Ok(())
+}
+
+pub async fn view_user<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let position = fetch_user_position(
+        program_client,
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+    )
+    .await?
+    .ok_or_else(|| anyhow!("user is not created"))?;
+    let user_state = &position.state;
+    println!("{:?}", user_state);
 
-    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
     println!(
-        "withdraw {} lp token partner {} {}",
-        unmint_amount, partner, partner_token
+        "lp token {}",
+        utils::format_amount(position.lp_token, vault.lp_decimals)
     );
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-    // check whether partner is existed
-    let _partner_state: affiliate::Partner = program_client.account(partner).await?;
-    let (user, _nonce) = Pubkey::find_program_address(
-        &[partner.as_ref(), program_client.payer().as_ref()],
-        &affiliate::id(),
+    println!(
+        "position {} at virtual price {}",
+        utils::format_amount(position.amount, vault.token_decimals),
+        position.virtual_price
     );
-    let user_lp = get_or_create_ata(program_client, lp_mint, user).await?;
-    // check whether user is existed
-    let rpc_client = program_client.rpc();
-    if rpc_client.get_account_data(&user).is_err() {
-        // create user account
-        let builder = program_client
-            .request()
-            .accounts(affiliate::accounts::InitUserPermissionless {
-                user,
-                partner,
-                owner: program_client.payer(),
-                payer: program_client.payer(),
-                system_program: system_program::id(),
-            })
-            .args(affiliate::instruction::InitUserPermissionless {});
+    for (name, amount) in [
+        ("cumulative yield", user_state.cumulative_yield()),
+        ("cumulative fee", user_state.cumulative_fee()),
+        ("total deposited", user_state.total_deposited()),
+        ("total withdrawn", user_state.total_withdrawn()),
+        ("outstanding rebate", user_state.outstanding_rebate()),
+    ] {
+        println!(
+            "{} {}",
+            name,
+            utils::format_amount(amount, vault.token_decimals)
+        );
+    }
 
-        let signature = builder.send().await?;
-        println!("create user {}", signature);
+    match user_state.fee_ratio_override() {
+        Some(fee_ratio) => println!("fee ratio {} (override)", fee_ratio),
+        None => println!("fee ratio {} (partner)", position.fee_ratio),
     }
 
-    let builder = program_client
-        .request()
-        .accounts(affiliate::accounts::DepositWithdrawLiquidity {
-            partner,
-            user,
-            vault: vault,
-            vault_program: mercurial_vault::id(),
-            token_vault: token_vault,
-            vault_lp_mint: lp_mint,
-            user_token,
-            user_lp,
-            owner: program_client.payer(),
-            token_program: spl_token::id(),
+    Ok(())
+}
+
+pub async fn view_rebate<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let partner_state = fetch_partner(program_client, partner).await?;
+
+    // check whether user is existed
+    let user_state = fetch_user(
+        program_client,
+        pda::derive_user(partner, program_client.payer()),
+    )
+    .await?
+    .ok_or_else(|| anyhow!("user is not created"))?;
+
+    let rebate_escrow = pda::derive_rebate_escrow(partner);
+    let escrow_amount = match program_client.rpc().get_account_data(&rebate_escrow) {
+        Ok(data) => spl_token::state::Account::unpack(&data)?.amount,
+        Err(_) => 0,
+    };
+    println!(
+        "rebate ratio {} outstanding rebate {} claimable {}",
+        partner_state.rebate_ratio,
+        user_state.outstanding_rebate(),
+        user_state.outstanding_rebate().min(escrow_amount)
+    );
+
+    Ok(())
+}
+
+// must be called by user, rebate is capped at balance of rebate escrow
+pub async fn claim_rebate<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    get_or_create_ata(program_client, vault.token_mint, program_client.payer()).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = pda::derive_user(partner, program_client.payer());
+
+    let builder = fetch_migrate_instructions(program_client, partner, Some(user))
+        .await?
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
         })
-        .args(affiliate::instruction::Withdraw {
-            unmint_amount,
-            min_out_amount: 0,
+        .instruction(instructions::claim_rebate(
+            &vault,
+            partner_wallet,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+// can be called by anyone, owner is the wallet of the user
+pub async fn refresh_points<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    owner: Option<String>,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let owner = owner.map_or(program_client.payer(), |owner| {
+        Pubkey::from_str(&owner).unwrap()
+    });
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = pda::derive_user(partner, owner);
+
+    let builder = fetch_migrate_instructions(program_client, partner, Some(user))
+        .await?
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
+        })
+        .instruction(instructions::refresh_points(partner, user));
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+
+    let user_state = fetch_user(program_client, user)
+        .await?
+        .ok_or_else(|| anyhow!("user is not created"))?;
+    println!("points {}", user_state.points());
+
+    Ok(())
+}
+
+pub async fn view_pending_fee<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+    payer: &Keypair,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = pda::derive_user(partner, program_client.payer());
+
+    // accounts still in borsh layout are migrated within the simulation
+    let builder = fetch_migrate_instructions(program_client, partner, Some(user))
+        .await?
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
+        })
+        .instruction(instructions::get_user_pending_fee(
+            &vault,
+            partner_wallet,
+            program_client.payer(),
+        ));
+
+    let simulation = utils::simulate_transaction(&builder, program_client, &vec![payer])
+        .map_err(|err| anyhow!("{}", err))?;
+    let logs = simulation
+        .value
+        .logs
+        .context("No log in simulation found")?;
+    let pending_fee: affiliate::UserPendingFee =
+        utils::parse_event_log(&logs).context("Event log not found")?;
+    println!(
+        "virtual price {} lp {} position {} pending yield {} pending fee {} rebate {}",
+        pending_fee.virtual_price,
+        pending_fee.lp_token,
+        pending_fee.position_amount,
+        pending_fee.pending_yield,
+        pending_fee.pending_fee,
+        pending_fee.outstanding_rebate
+    );
+
+    Ok(())
+}
+
+pub async fn close_user<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    partner: String,
+) -> Result<()> {
+    let partner_wallet = Pubkey::from_str(&partner).unwrap();
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = pda::derive_user(partner, program_client.payer());
+    let user_state = fetch_user(program_client, user)
+        .await?
+        .ok_or_else(|| anyhow!("user is not created"))?;
+
+    // claim rebate covered by rebate escrow before closing, the rest is forfeited
+    let mut builder = fetch_migrate_instructions(program_client, partner, Some(user))
+        .await?
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
         });
+    if user_state.outstanding_rebate() > 0 {
+        get_or_create_ata(program_client, vault.token_mint, program_client.payer()).await?;
+        builder = builder.instruction(instructions::claim_rebate(
+            &vault,
+            partner_wallet,
+            program_client.payer(),
+        ));
+    }
+    let builder = builder.instruction(instructions::close_user(
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+    ));
 
     let signature = builder.send().await?;
     println!("{}", signature);
@@ -172,52 +484,105 @@ pub async fn withdraw<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
-pub async fn view_user<C: Deref<Target = impl Signer> + Clone>(
+// simulate deposit and withdraw of payer without sending them and print the compute units, accounts
+// are not migrated so the layout they are in is measured, user of payer must be created
+pub async fn measure_compute_units<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,
     partner: String,
+    token_amount: u64,
+    unmint_amount: u64,
+    payer: &Keypair,
 ) -> Result<()> {
-    let partner = Pubkey::from_str(&partner).unwrap();
-    let vault_state: mercurial_vault::state::Vault = program_client.account(vault).await?;
-    let token_mint = vault_state.token_mint;
-    let partner_token = get_or_create_ata(program_client, token_mint, partner).await?;
-    let (partner, _nonce) =
-        Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &affiliate::id());
-
-    let (user, _nonce) = Pubkey::find_program_address(
-        &[partner.as_ref(), program_client.payer().as_ref()],
-        &affiliate::id(),
+    let partner_wallet = Pubkey::from_str(&partner)?;
+    let vault = fetch_vault(program_client, vault).await?;
+    let partner = get_partner(&vault, partner_wallet);
+    let user = pda::derive_user(partner, program_client.payer());
+    if fetch_user(program_client, user).await?.is_none() {
+        return Err(anyhow!("user is not created"));
+    }
+    get_or_create_ata(program_client, vault.lp_mint, user).await?;
+    if !vault.is_native() {
+        get_or_create_ata(program_client, vault.token_mint, program_client.payer()).await?;
+    }
+    println!(
+        "partner borsh layout {} user borsh layout {}",
+        fetch_partner_needs_migration(program_client, partner).await?,
+        fetch_user_needs_migration(program_client, user).await?
+    );
+
+    let signers: Vec<&dyn Signer> = vec![payer];
+    let builder = program_client.request().instruction(instructions::deposit(
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+        token_amount,
+        0,
+        None,
+    ));
+    let deposit_units = utils::simulate_compute_units(&builder, program_client, &signers)?;
+    println!("deposit {} compute units {}", token_amount, deposit_units);
+
+    let builder = program_client.request().instruction(instructions::withdraw(
+        &vault,
+        partner_wallet,
+        program_client.payer(),
+        unmint_amount,
+        0,
+        None,
+    ));
+    let withdraw_units = utils::simulate_compute_units(&builder, program_client, &signers)?;
+    println!(
+        "withdraw {} compute units {}",
+        unmint_amount, withdraw_units
     );
-    // check whether user is existed
-    let user_state: affiliate::User = program_client.account(user).await?;
-    println!("{:?}", user_state);
 
     Ok(())
 }
 
-pub async fn get_or_create_ata<C: Deref<Target = impl Signer> + Clone>(
+// expected lp minted for token amount, reduced by slippage
+pub async fn get_minimum_lp_token_amount<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
-    token_mint: Pubkey,
-    user: Pubkey,
-) -> Result<Pubkey> {
-    let user_token_account =
-        spl_associated_token_account::get_associated_token_address(&user, &token_mint);
-    let rpc_client = program_client.rpc();
-    if rpc_client.get_account_data(&user_token_account).is_err() {
-        println!("Create ATA for TOKEN {} \n", &token_mint);
-
-        let builder = program_client.request().instruction(
-            spl_associated_token_account::create_associated_token_account(
-                &program_client.payer(),
-                &user,
-                &token_mint,
-            ),
-        );
+    vault: &VaultInfo,
+    token_amount: u64,
+    slippage: Slippage,
+) -> Result<u64> {
+    if slippage.min_out.is_some() {
+        return slippage.get_min_out(0);
+    }
+    let lp_mint: anchor_spl::token::Mint = program_client.account(vault.lp_mint).await?;
+    // first deposit mints lp 1:1
+    if lp_mint.supply == 0 {
+        return slippage.get_min_out(token_amount.into());
+    }
+    let virtual_price = fetch_virtual_price(program_client, vault).await?;
+    let expected_lp = get_expected_lp(token_amount, virtual_price)
+        .ok_or_else(|| anyhow!("expected lp overflows"))?;
+    println!(
+        "expected lp {} at virtual price {}, slippage {} bps",
+        expected_lp, virtual_price, slippage.slippage_bps
+    );
+    slippage.get_min_out(expected_lp)
+}
 
-        let signature = builder.send().await?;
-        println!("{}", signature);
+// expected token amount withdrawn for lp, reduced by slippage
+pub async fn get_min_out_amount<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: &VaultInfo,
+    unmint_amount: u64,
+    slippage: Slippage,
+) -> Result<u64> {
+    if slippage.min_out.is_some() {
+        return slippage.get_min_out(0);
     }
-    Ok(user_token_account)
+    let virtual_price = fetch_virtual_price(program_client, vault).await?;
+    let expected_amount = get_expected_amount(unmint_amount, virtual_price)
+        .ok_or_else(|| anyhow!("expected amount overflows"))?;
+    println!(
+        "expected amount {} at virtual price {}, slippage {} bps",
+        expected_amount, virtual_price, slippage.slippage_bps
+    );
+    slippage.get_min_out(expected_amount)
 }
 
 pub async fn create_mint<C: Deref<Target = impl Signer> + Clone>(
@@ -1,3 +1,6 @@
+pub use affiliate_sdk::amount::{format_amount, parse_amount};
+use affiliate_sdk::types::VaultInfo;
+use affiliate_sdk::{accounts, instructions, pda};
 use anchor_client::solana_client::rpc_response::RpcSimulateTransactionResult;
 use anchor_client::Client;
 use anchor_client::{
@@ -6,7 +9,7 @@ use anchor_client::{
     Program,
 };
 use anchor_client::{Cluster, RequestBuilder};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
@@ -57,6 +60,27 @@ pub fn simulate_transaction<C: Deref<Target = impl Signer> + Clone>(
     Ok(simulation)
 }
 
+// compute units consumed by a simulated transaction, fails if the simulation fails
+pub fn simulate_compute_units<C: Deref<Target = impl Signer> + Clone>(
+    builder: &RequestBuilder<C>,
+    program: &Program<C>,
+    signers: &Vec<&dyn Signer>,
+) -> Result<u64> {
+    let simulation =
+        simulate_transaction(builder, program, signers).map_err(|err| anyhow!("{}", err))?;
+    if let Some(err) = simulation.value.err {
+        return Err(anyhow!(
+            "simulation failed: {} {:?}",
+            err,
+            simulation.value.logs.unwrap_or_default()
+        ));
+    }
+    simulation
+        .value
+        .units_consumed
+        .ok_or_else(|| anyhow!("compute units not returned by simulation"))
+}
+
 pub async fn get_or_create_ata<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     token_mint: Pubkey,
@@ -82,6 +106,129 @@ pub async fn get_or_create_ata<C: Deref<Target = impl Signer> + Clone>(
     Ok(user_token_account)
 }
 
+// partner PDA of partner wallet in vault, partner token account is created if missing
+pub async fn get_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: &VaultInfo,
+    partner_wallet: Pubkey,
+) -> Result<Pubkey> {
+    let partner_token = get_or_create_ata(program_client, vault.token_mint, partner_wallet).await?;
+    Ok(pda::derive_partner(vault.vault, partner_token))
+}
+
+// migrate partner still in borsh layout, instructions loading the partner require zero-copy layout
+pub async fn migrate_partner_if_needed<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+) -> Result<()> {
+    let migrate_instructions =
+        accounts::fetch_migrate_instructions(program_client, partner, None).await?;
+    if migrate_instructions.is_empty() {
+        return Ok(());
+    }
+    println!("migrate partner {}", partner);
+    let builder = migrate_instructions
+        .into_iter()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
+        });
+
+    let signature = builder.send().await?;
+    println!("{}", signature);
+    Ok(())
+}
+
+// migrate every user of partner still in borsh layout
+pub async fn migrate_partner_users<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Pubkey,
+) -> Result<()> {
+    let legacy_users: Vec<Pubkey> = accounts::fetch_partner_users(program_client, partner)
+        .await?
+        .into_iter()
+        .filter(|(_, _, legacy)| *legacy)
+        .map(|(user, _, _)| user)
+        .collect();
+    for chunk in legacy_users.chunks(MIGRATE_USER_BATCH_SIZE) {
+        let builder = chunk
+            .iter()
+            .fold(program_client.request(), |builder, user| {
+                builder.instruction(instructions::migrate_user(*user))
+            });
+        let signature = builder.send().await?;
+        println!("migrate {} users {}", chunk.len(), signature);
+    }
+    Ok(())
+}
+
+// migrate_user instructions packed in one transaction
+const MIGRATE_USER_BATCH_SIZE: usize = 10;
+
+pub async fn get_mint_decimals<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    mint: Pubkey,
+) -> Result<u8> {
+    let mint: anchor_spl::token::Mint = program_client.account(mint).await?;
+    Ok(mint.decimals)
+}
+
+// balance of token account, zero if token account is not created
+pub async fn get_token_balance<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    token_account: Pubkey,
+) -> Result<u64> {
+    if program_client
+        .rpc()
+        .get_account_data(&token_account)
+        .is_err()
+    {
+        return Ok(0);
+    }
+    let token_account: anchor_spl::token::TokenAccount =
+        program_client.account(token_account).await?;
+    Ok(token_account.amount)
+}
+
+// resolve partner wallet from either partner address or referral code
+pub async fn resolve_partner<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    partner: Option<String>,
+    code: Option<String>,
+) -> Result<String> {
+    match (partner, code) {
+        (Some(partner), None) => Ok(partner),
+        (None, Some(code)) => {
+            let referral_code = pda::derive_referral_code(&code);
+            let referral_code_state: affiliate::ReferralCode =
+                program_client.account(referral_code).await?;
+            println!(
+                "referral code {} partner {}",
+                code, referral_code_state.partner_wallet
+            );
+            Ok(referral_code_state.partner_wallet.to_string())
+        }
+        _ => Err(anyhow!("Either partner or --code must be provided")),
+    }
+}
+
+// role signer signs admin instructions instead of payer, so operations never need the root key
+pub fn get_authority<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    role_signer: Option<&Keypair>,
+) -> Pubkey {
+    role_signer.map_or(program_client.payer(), |signer| signer.pubkey())
+}
+
+pub fn with_role_signer<'a, C: Deref<Target = impl Signer> + Clone>(
+    builder: RequestBuilder<'a, C>,
+    role_signer: Option<&'a Keypair>,
+) -> RequestBuilder<'a, C> {
+    match role_signer {
+        Some(signer) => builder.signer(signer),
+        None => builder,
+    }
+}
+
 pub fn default_keypair() -> Keypair {
     read_keypair_file(&*shellexpand::tilde("~/.config/solana/id.json"))
         .expect("Requires a keypair file")
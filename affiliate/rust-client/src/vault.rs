@@ -1,12 +1,9 @@
 use crate::utils;
+use affiliate_sdk::accounts::fetch_clock_time;
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
-use bincode::deserialize;
-use solana_program::sysvar;
-use solana_program::sysvar::clock::Clock;
 use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
-use std::convert::TryFrom;
 use std::ops::Deref;
 
 pub async fn show<C: Deref<Target = impl Signer> + Clone>(
@@ -16,20 +13,27 @@ pub async fn show<C: Deref<Target = impl Signer> + Clone>(
     let vault_data: mercurial_vault::state::Vault = program_client.account(vault).await?;
     println!("VAULT DATA: {:#?}", vault_data);
     let token_mint: anchor_spl::token::Mint = program_client.account(vault_data.lp_mint).await?;
+    let token_decimals = utils::get_mint_decimals(program_client, vault_data.token_mint).await?;
 
-    let current_timestamp = get_current_node_clock_time(program_client)?;
+    let current_timestamp = fetch_clock_time(program_client).await?;
 
     println!(
         "TOTAL_AMOUNT: {}, TOTAL_UNLOCKED_AMOUNT: {}, lp_mint {}",
-        vault_data.total_amount,
-        vault_data.get_unlocked_amount(current_timestamp).unwrap(),
-        token_mint.supply
+        utils::format_amount(vault_data.total_amount, token_decimals),
+        utils::format_amount(
+            vault_data.get_unlocked_amount(current_timestamp).unwrap(),
+            token_decimals
+        ),
+        utils::format_amount(token_mint.supply, token_mint.decimals)
     );
 
     let token_data: anchor_spl::token::TokenAccount =
         program_client.account(vault_data.token_vault).await?;
 
-    println!("TOKEN AMOUNT: {}", token_data.amount);
+    println!(
+        "TOKEN AMOUNT: {}",
+        utils::format_amount(token_data.amount, token_decimals)
+    );
 
     let mut strategy_amount = 0u64;
     for (i, &strategy_pubkey) in vault_data.strategies.iter().enumerate() {
@@ -47,16 +51,6 @@ pub async fn show<C: Deref<Target = impl Signer> + Clone>(
     Ok(())
 }
 
-pub fn get_current_node_clock_time<C: Deref<Target = impl Signer> + Clone>(
-    program_client: &anchor_client::Program<C>,
-) -> Result<u64> {
-    let rpc = program_client.rpc();
-    let clock_account = rpc.get_account(&sysvar::clock::id())?;
-    let clock = deserialize::<Clock>(&clock_account.data)?;
-    let current_time = u64::try_from(clock.unix_timestamp)?;
-    Ok(current_time)
-}
-
 pub fn get_unlocked_amount<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     vault: Pubkey,